@@ -0,0 +1,754 @@
+//! Reading and writing append only files.
+//!
+//! An AOF is the list of write commands, as arrays of bulk strings, optionally preceded by an
+//! RDB preamble holding the dataset at the time of the last rewrite. Since Redis 7 the AOF is
+//! split into a base file and incremental files, listed in a [`Manifest`].
+
+use crate::client::encode_command;
+use crate::rdb::{RdbEvent, RdbParser, RdbValue};
+use crate::reader::ReadBuffer;
+use crate::{parse_resp, Resp, RespError};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Component, Path};
+
+/// The content of an append only file.
+#[derive(Debug, PartialEq)]
+pub struct Aof {
+    /// The records of the RDB preamble, empty if there is none.
+    pub preamble: Vec<RdbEvent>,
+    /// The arguments of every command, starting with its name.
+    pub commands: Vec<Vec<Vec<u8>>>,
+    /// The length of the input that was loaded, which is shorter than the input when its tail
+    /// was truncated. Like `redis-check-aof --fix`, truncating the file to this length repairs
+    /// it.
+    pub valid_len: usize,
+    pub truncated: bool,
+}
+
+/// A record of an append only file.
+#[derive(Debug, PartialEq)]
+pub enum AofRecord {
+    /// A record of the RDB preamble.
+    Preamble(RdbEvent),
+    /// The arguments of a command, starting with its name.
+    Command(Vec<Vec<u8>>),
+}
+
+#[derive(Debug)]
+enum State {
+    // Until there are enough bytes to look for an RDB preamble
+    Start,
+    Preamble(RdbParser),
+    Commands,
+}
+
+/// Reads an append only file one record at a time, without loading it in memory.
+///
+/// A command cut short at the end of the file is dropped, as Redis does with
+/// `aof-load-truncated yes`, along with the rest of a `MULTI` that never got its `EXEC`, so
+/// the commands of a transaction are only yielded once its `EXEC` has been read. A malformed
+/// command anywhere else is an error. Annotations, the `#` lines written with
+/// `aof-timestamp-enabled`, are skipped.
+///
+/// Once the iterator is exhausted, [`AofReader::valid_len`] and [`AofReader::is_truncated`]
+/// tell whether the tail of the file was dropped. Iteration stops after the first error.
+#[derive(Debug)]
+pub struct AofReader<R> {
+    reader: R,
+    buffer: ReadBuffer,
+    state: State,
+    // The commands of a transaction whose `EXEC` has not been read yet
+    multi: Option<Vec<Vec<Vec<u8>>>>,
+    // The commands of a complete transaction, still to be yielded
+    ready: VecDeque<Vec<Vec<u8>>>,
+    // Bytes consumed so far
+    pos: u64,
+    valid_len: u64,
+    eof: bool,
+    done: bool,
+    failed: bool,
+}
+
+impl<R: Read> AofReader<R> {
+    /// The reader is buffered internally, a [`File`] can be passed as is.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: ReadBuffer::default(),
+            state: State::Start,
+            multi: None,
+            ready: VecDeque::new(),
+            pos: 0,
+            valid_len: 0,
+            eof: false,
+            done: false,
+            failed: false,
+        }
+    }
+
+    /// The length of the input loaded so far. Once the iterator is exhausted, truncating the
+    /// file to this length repairs it, like `redis-check-aof --fix`.
+    pub fn valid_len(&self) -> u64 {
+        self.valid_len
+    }
+
+    /// Whether a truncated tail was dropped, which is only known once the iterator is
+    /// exhausted.
+    pub fn is_truncated(&self) -> bool {
+        self.done && self.valid_len < self.pos + self.buffer.buffered().len() as u64
+    }
+
+    pub fn read_record(&mut self) -> Result<Option<AofRecord>, RespError> {
+        loop {
+            if let Some(args) = self.ready.pop_front() {
+                return Ok(Some(AofRecord::Command(args)));
+            }
+            if self.done {
+                return Ok(None);
+            }
+            match self.parse_record() {
+                Ok(Some(record)) => return Ok(Some(record)),
+                Ok(None) => {}
+                Err(RespError::NotEnoughBytes) if self.eof => match self.state {
+                    State::Preamble(_) => return Err(RespError::IncorrectFormat),
+                    _ => self.done = true,
+                },
+                Err(RespError::NotEnoughBytes) => {
+                    self.eof = !self.buffer.fill(&mut self.reader)?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.buffer.consume(len);
+        self.pos += len as u64;
+    }
+
+    // Parses what is buffered, returning `None` for what is not yielded as a record.
+    fn parse_record(&mut self) -> Result<Option<AofRecord>, RespError> {
+        let input = self.buffer.buffered();
+        match &mut self.state {
+            State::Start => {
+                if input.len() < 5 && !self.eof {
+                    return Err(RespError::NotEnoughBytes);
+                }
+                self.state = match input.starts_with(b"REDIS") {
+                    true => State::Preamble(RdbParser::new()),
+                    false => State::Commands,
+                };
+                Ok(None)
+            }
+            State::Preamble(parser) => {
+                let (event, leftover) = parser.next_event(input)?;
+                let len = input.len() - leftover.len();
+                if matches!(event, RdbEvent::End { .. }) {
+                    self.state = State::Commands;
+                }
+                self.consume(len);
+                self.valid_len = self.pos;
+                Ok(Some(AofRecord::Preamble(event)))
+            }
+            State::Commands if input.first() == Some(&b'#') => {
+                let len = input
+                    .windows(2)
+                    .position(|w| w == b"\r\n")
+                    .ok_or(RespError::NotEnoughBytes)?;
+                self.consume(len + 2);
+                if self.multi.is_none() {
+                    self.valid_len = self.pos;
+                }
+                Ok(None)
+            }
+            State::Commands => {
+                let (command, leftover) = parse_resp(input)?;
+                let len = input.len() - leftover.len();
+                let args = match command {
+                    Resp::Array(args) if !args.is_empty() => args
+                        .into_iter()
+                        .map(|arg| match arg {
+                            Resp::BulkString(arg) => Ok(arg.to_vec()),
+                            _ => Err(RespError::IncorrectFormat),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?,
+                    _ => return Err(RespError::IncorrectFormat),
+                };
+                self.consume(len);
+                let name = args[0].to_ascii_uppercase();
+                if name == b"MULTI" {
+                    // Redis never nests transactions, so a MULTI inside one gives the open
+                    // transaction back as is and starts a new one
+                    if let Some(commands) = self.multi.take() {
+                        self.ready.extend(commands);
+                    }
+                    self.multi = Some(vec![args]);
+                    return Ok(None);
+                }
+                let Some(commands) = self.multi.as_mut() else {
+                    self.valid_len = self.pos;
+                    return Ok(Some(AofRecord::Command(args)));
+                };
+                let exec = name == b"EXEC";
+                commands.push(args);
+                if exec {
+                    self.ready.extend(self.multi.take().unwrap_or_default());
+                    self.valid_len = self.pos;
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for AofReader<R> {
+    type Item = Result<AofRecord, RespError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let record = self.read_record().transpose();
+        self.failed = matches!(record, Some(Err(_)));
+        record
+    }
+}
+
+/// Parses an append only file held in memory, see [`AofReader`].
+pub fn parse_aof(input: &[u8]) -> Result<Aof, RespError> {
+    let mut reader = AofReader::new(input);
+    let (mut preamble, mut commands) = (Vec::new(), Vec::new());
+    for record in &mut reader {
+        match record? {
+            AofRecord::Preamble(event) => preamble.push(event),
+            AofRecord::Command(args) => commands.push(args),
+        }
+    }
+    Ok(Aof {
+        preamble,
+        commands,
+        valid_len: reader.valid_len() as usize,
+        truncated: reader.is_truncated(),
+    })
+}
+
+/// The kind of a file of a multi part AOF.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AofFileKind {
+    /// The file written by the last rewrite, either an RDB or an AOF.
+    Base,
+    /// Commands appended since the base file.
+    Incr,
+    /// A file replaced by a rewrite, waiting to be deleted.
+    History,
+}
+
+impl AofFileKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AofFileKind::Base => "b",
+            AofFileKind::Incr => "i",
+            AofFileKind::History => "h",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ManifestEntry {
+    /// A file name in the directory of the manifest, never a path.
+    pub name: String,
+    pub seq: u64,
+    pub kind: AofFileKind,
+}
+
+/// The manifest of a multi part AOF, `appendonly.aof.manifest` in the `appenddirname`
+/// directory.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+// Splits a line into words, which may be quoted like with `sdssplitargs`.
+fn split_words(line: &str) -> Result<Vec<String>, RespError> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(first) = chars.next() else {
+            return Ok(words);
+        };
+        let mut word = String::new();
+        if first == '"' {
+            loop {
+                match chars.next().ok_or(RespError::IncorrectFormat)? {
+                    '"' => break,
+                    '\\' => word.push(chars.next().ok_or(RespError::IncorrectFormat)?),
+                    c => word.push(c),
+                }
+            }
+        } else {
+            word.push(first);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                word.push(c);
+            }
+        }
+        words.push(word);
+    }
+}
+
+// Whether `name` stays in the directory it is joined to. Redis rejects any `/` as well.
+fn is_file_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    !name.contains('/')
+        && matches!(components.next(), Some(Component::Normal(_)))
+        && components.next().is_none()
+}
+
+impl Manifest {
+    /// Parses the lines of `key value` pairs describing each file. Unknown keys are ignored,
+    /// as are comments.
+    pub fn parse(input: &str) -> Result<Self, RespError> {
+        let mut entries = Vec::new();
+        for line in input.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let words = split_words(line)?;
+            if words.len() % 2 == 1 {
+                return Err(RespError::IncorrectFormat);
+            }
+            let (mut name, mut seq, mut kind) = (None, None, None);
+            for pair in words.chunks(2) {
+                match pair[0].as_str() {
+                    "file" => name = Some(pair[1].clone()),
+                    "seq" => seq = Some(pair[1].parse()?),
+                    "type" => {
+                        kind = Some(match pair[1].as_str() {
+                            "b" => AofFileKind::Base,
+                            "i" => AofFileKind::Incr,
+                            "h" => AofFileKind::History,
+                            _ => return Err(RespError::IncorrectFormat),
+                        })
+                    }
+                    _ => {}
+                }
+            }
+            match (name, seq, kind) {
+                (Some(name), Some(seq), Some(kind)) if is_file_name(&name) => {
+                    entries.push(ManifestEntry { name, seq, kind })
+                }
+                _ => return Err(RespError::IncorrectFormat),
+            }
+        }
+        Ok(Manifest { entries })
+    }
+
+    /// The files to load, the base file first then the incremental files by sequence.
+    pub fn load_order(&self) -> Vec<&ManifestEntry> {
+        let mut files: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|e| e.kind != AofFileKind::History)
+            .collect();
+        files.sort_by_key(|e| (e.kind != AofFileKind::Base, e.seq));
+        files
+    }
+
+    pub fn write_to_writer<W: Write>(&self, writer: &mut W) -> Result<(), RespError> {
+        for entry in &self.entries {
+            let name = if entry.name.contains(|c: char| c.is_whitespace() || c == '"') {
+                format!(
+                    "\"{}\"",
+                    entry.name.replace('\\', "\\\\").replace('"', "\\\"")
+                )
+            } else {
+                entry.name.clone()
+            };
+            writeln!(
+                writer,
+                "file {} seq {} type {}",
+                name,
+                entry.seq,
+                entry.kind.as_str()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the manifest of a multi part AOF, then opens the files to load, in order.
+pub fn read_multi_part(
+    dir: &Path,
+    manifest: &str,
+) -> Result<Vec<(ManifestEntry, AofReader<File>)>, RespError> {
+    let manifest = std::fs::read_to_string(dir.join(manifest))?;
+    Manifest::parse(&manifest)?
+        .load_order()
+        .into_iter()
+        .map(|entry| {
+            let file = File::open(dir.join(&entry.name))?;
+            Ok((entry.clone(), AofReader::new(file)))
+        })
+        .collect()
+}
+
+/// Appends commands to an AOF, or rewrites one from a dataset.
+#[derive(Debug)]
+pub struct AofWriter<W: Write> {
+    writer: W,
+    db: Option<u64>,
+}
+
+impl<W: Write> AofWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer, db: None }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    pub fn command<A: AsRef<[u8]>>(&mut self, args: &[A]) -> Result<(), RespError> {
+        encode_command(args, &mut self.writer)
+    }
+
+    /// Writes a `SELECT` if the following commands are for another database than the
+    /// previous ones.
+    pub fn select(&mut self, db: u64) -> Result<(), RespError> {
+        if self.db != Some(db) {
+            self.command(&[b"SELECT".to_vec(), db.to_string().into_bytes()])?;
+            self.db = Some(db);
+        }
+        Ok(())
+    }
+
+    /// Writes the commands recreating a key, the way `BGREWRITEAOF` does.
+    pub fn entry(
+        &mut self,
+        key: &[u8],
+        value: &RdbValue,
+        expire_ms: Option<u64>,
+    ) -> Result<(), RespError> {
+        let key = key.to_vec();
+        match value {
+            RdbValue::String(s) => self.command(&[b"SET".to_vec(), key.clone(), s.clone()])?,
+            RdbValue::List(items) | RdbValue::Set(items) => {
+                let name = match value {
+                    RdbValue::List(_) => b"RPUSH".to_vec(),
+                    _ => b"SADD".to_vec(),
+                };
+                let mut args = vec![name, key.clone()];
+                args.extend(items.iter().cloned());
+                self.command(&args)?;
+            }
+            RdbValue::SortedSet(members) => {
+                let mut args = vec![b"ZADD".to_vec(), key.clone()];
+                for (member, score) in members {
+                    args.push(score.to_string().into_bytes());
+                    args.push(member.clone());
+                }
+                self.command(&args)?;
+            }
+            RdbValue::Hash(fields) => {
+                let mut args = vec![b"HSET".to_vec(), key.clone()];
+                for (field, value) in fields {
+                    args.push(field.clone());
+                    args.push(value.clone());
+                }
+                self.command(&args)?;
+            }
+            RdbValue::Stream(stream) => {
+                let id = |id: crate::streams::StreamId| id.to_string().into_bytes();
+                if stream.entries.is_empty() {
+                    // Creates the key without leaving an entry
+                    self.command(&[
+                        b"XADD".to_vec(),
+                        key.clone(),
+                        b"MAXLEN".to_vec(),
+                        b"0".to_vec(),
+                        id(stream.last_id),
+                        b"x".to_vec(),
+                        b"y".to_vec(),
+                    ])?;
+                }
+                for (entry_id, fields) in &stream.entries {
+                    let mut args = vec![b"XADD".to_vec(), key.clone(), id(*entry_id)];
+                    for (field, value) in fields {
+                        args.push(field.clone());
+                        args.push(value.clone());
+                    }
+                    self.command(&args)?;
+                }
+                let mut args = vec![b"XSETID".to_vec(), key.clone(), id(stream.last_id)];
+                if let Some(added) = stream.entries_added {
+                    args.push(b"ENTRIESADDED".to_vec());
+                    args.push(added.to_string().into_bytes());
+                }
+                if let Some(max_deleted_id) = stream.max_deleted_id {
+                    args.push(b"MAXDELETEDID".to_vec());
+                    args.push(id(max_deleted_id));
+                }
+                self.command(&args)?;
+                for group in &stream.groups {
+                    let mut args = vec![
+                        b"XGROUP".to_vec(),
+                        b"CREATE".to_vec(),
+                        key.clone(),
+                        group.name.clone(),
+                        id(group.last_id),
+                    ];
+                    if let Some(read) = group.entries_read {
+                        args.push(b"ENTRIESREAD".to_vec());
+                        args.push(read.to_string().into_bytes());
+                    }
+                    self.command(&args)?;
+                    for consumer in &group.consumers {
+                        self.command(&[
+                            b"XGROUP".to_vec(),
+                            b"CREATECONSUMER".to_vec(),
+                            key.clone(),
+                            group.name.clone(),
+                            consumer.name.clone(),
+                        ])?;
+                        for pending_id in &consumer.pending {
+                            let Some(pending) = group.pending.iter().find(|p| p.id == *pending_id)
+                            else {
+                                continue;
+                            };
+                            self.command(&[
+                                b"XCLAIM".to_vec(),
+                                key.clone(),
+                                group.name.clone(),
+                                consumer.name.clone(),
+                                b"0".to_vec(),
+                                id(pending.id),
+                                b"TIME".to_vec(),
+                                pending.delivery_time.to_string().into_bytes(),
+                                b"RETRYCOUNT".to_vec(),
+                                pending.delivery_count.to_string().into_bytes(),
+                                b"JUSTID".to_vec(),
+                                b"FORCE".to_vec(),
+                            ])?;
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(ms) = expire_ms {
+            self.command(&[b"PEXPIREAT".to_vec(), key, ms.to_string().into_bytes()])?;
+        }
+        Ok(())
+    }
+
+    /// Writes the commands recreating the dataset of an RDB file, such as the preamble of an
+    /// AOF, to get an AOF without preamble.
+    pub fn rewrite<I: IntoIterator<Item = RdbEvent>>(
+        &mut self,
+        events: I,
+    ) -> Result<(), RespError> {
+        for event in events {
+            match event {
+                RdbEvent::SelectDb(db) => self.select(db)?,
+                RdbEvent::Function(code) => {
+                    self.command(&[b"FUNCTION".to_vec(), b"LOAD".to_vec(), code])?
+                }
+                RdbEvent::Entry {
+                    key,
+                    value,
+                    expire_ms,
+                    ..
+                } => {
+                    // Keys of a file without SELECTDB belong to the first database
+                    if self.db.is_none() {
+                        self.select(0)?;
+                    }
+                    self.entry(&key, &value, expire_ms)?
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), RespError> {
+        Ok(self.writer.flush()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rdb::{crc64, RdbStream};
+    use crate::reader::test::Trickle;
+    use crate::streams::StreamId;
+
+    fn commands(commands: &[&[&str]]) -> Vec<Vec<Vec<u8>>> {
+        commands
+            .iter()
+            .map(|args| args.iter().map(|arg| arg.as_bytes().to_vec()).collect())
+            .collect()
+    }
+
+    #[test]
+    pub fn test_parse() {
+        let input = b"*2\r\n$6\r\nSELECT\r\n$1\r\n0\r\n#TS:1700000000\r\n*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n";
+        let aof = parse_aof(input).unwrap();
+        assert_eq!(
+            aof.commands,
+            commands(&[&["SELECT", "0"], &["SET", "k", "v"]])
+        );
+        assert!(!aof.truncated);
+
+        // A truncated tail, then an unfinished transaction, are dropped
+        let mut truncated = input.to_vec();
+        truncated.extend_from_slice(b"*1\r\n$4\r\nINCR");
+        let aof = parse_aof(&truncated).unwrap();
+        assert_eq!(aof.commands.len(), 2);
+        assert_eq!(aof.valid_len, input.len());
+        assert!(aof.truncated);
+        let mut truncated = input.to_vec();
+        truncated.extend_from_slice(b"*1\r\n$5\r\nMULTI\r\n*2\r\n$4\r\nINCR\r\n$1\r\nk\r\n");
+        let aof = parse_aof(&truncated).unwrap();
+        assert_eq!(aof.commands.len(), 2);
+        assert_eq!(aof.valid_len, input.len());
+        assert!(aof.truncated);
+
+        assert!(parse_aof(b"+OK\r\n").is_err());
+        assert!(parse_aof(b"*1\r\n:1\r\n").is_err());
+    }
+
+    #[test]
+    pub fn test_reader() {
+        let input = b"*1\r\n$5\r\nMULTI\r\n#TS:1\r\n*2\r\n$4\r\nINCR\r\n$1\r\nk\r\n*1\r\n$4\r\nEXEC\r\n*1\r\n$5\r\nMULTI\r\n*1\r\n$4\r\nPING\r\n";
+        for step in [1, 7, 1024] {
+            let mut reader = AofReader::new(Trickle { input, step });
+            let records = reader.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+            let expected = commands(&[&["MULTI"], &["INCR", "k"], &["EXEC"]]);
+            assert_eq!(
+                records,
+                expected
+                    .into_iter()
+                    .map(AofRecord::Command)
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(reader.valid_len(), 57);
+            assert!(reader.is_truncated());
+        }
+        let mut reader = AofReader::new(&b"*1\r\n:1\r\n*1\r\n$4\r\nPING\r\n"[..]);
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    pub fn test_preamble() {
+        let mut input = b"REDIS0011\xFE\x00\x00\x01k\x01v\xFF".to_vec();
+        let crc = crc64(0, &input);
+        input.extend_from_slice(&crc.to_le_bytes());
+        input.extend_from_slice(b"*2\r\n$3\r\nDEL\r\n$1\r\nk\r\n");
+        let aof = parse_aof(&input).unwrap();
+        assert_eq!(aof.preamble.len(), 4);
+        assert_eq!(aof.commands, commands(&[&["DEL", "k"]]));
+
+        let mut output = Vec::new();
+        let mut writer = AofWriter::new(&mut output);
+        writer.rewrite(aof.preamble).unwrap();
+        assert_eq!(
+            output,
+            b"*2\r\n$6\r\nSELECT\r\n$1\r\n0\r\n*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n".to_vec()
+        );
+        assert!(parse_aof(&input[..12]).is_err());
+    }
+
+    #[test]
+    pub fn test_writer() {
+        let mut output = Vec::new();
+        let mut writer = AofWriter::new(&mut output);
+        writer.select(1).unwrap();
+        writer.select(1).unwrap();
+        let value = RdbValue::SortedSet(vec![(b"m".to_vec(), 1.5)]);
+        writer.entry(b"z", &value, Some(1700000000000)).unwrap();
+        let stream = RdbStream {
+            entries: vec![(
+                StreamId { ms: 1, seq: 0 },
+                vec![(b"f".to_vec(), b"v".to_vec())],
+            )],
+            length: 1,
+            last_id: StreamId { ms: 1, seq: 0 },
+            ..RdbStream::default()
+        };
+        writer.entry(b"s", &RdbValue::Stream(stream), None).unwrap();
+        let aof = parse_aof(&output).unwrap();
+        assert_eq!(
+            aof.commands,
+            commands(&[
+                &["SELECT", "1"],
+                &["ZADD", "z", "1.5", "m"],
+                &["PEXPIREAT", "z", "1700000000000"],
+                &["XADD", "s", "1-0", "f", "v"],
+                &["XSETID", "s", "1-0"],
+            ])
+        );
+    }
+
+    #[test]
+    pub fn test_manifest() {
+        let input = "file appendonly.aof.2.incr.aof seq 2 type i\nfile appendonly.aof.1.base.rdb seq 1 type b\nfile \"my file.aof\" seq 1 type i\nfile appendonly.aof.0.base.rdb seq 0 type h\n";
+        let manifest = Manifest::parse(input).unwrap();
+        let names: Vec<_> = manifest
+            .load_order()
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "appendonly.aof.1.base.rdb",
+                "my file.aof",
+                "appendonly.aof.2.incr.aof"
+            ]
+        );
+        let mut output = Vec::new();
+        manifest.write_to_writer(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), input);
+        assert!(Manifest::parse("file a seq 1").is_err());
+        assert!(Manifest::parse("file a seq 1 type x").is_err());
+        for name in ["../a", "/tmp/a", "dir/a", "..", "."] {
+            let input = format!("file {} seq 1 type b", name);
+            assert!(matches!(
+                Manifest::parse(&input),
+                Err(RespError::IncorrectFormat)
+            ));
+        }
+    }
+
+    #[test]
+    pub fn test_read_multi_part() {
+        let dir = std::env::temp_dir().join(format!("aof-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("appendonly.aof.manifest"),
+            "file appendonly.aof.1.base.aof seq 1 type b\nfile appendonly.aof.1.incr.aof seq 1 type i\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("appendonly.aof.1.base.aof"),
+            b"*1\r\n$4\r\nPING\r\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("appendonly.aof.1.incr.aof"), b"").unwrap();
+        let mut files = read_multi_part(&dir, "appendonly.aof.manifest").unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].0.kind, AofFileKind::Base);
+        let records = files[0].1.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            records,
+            vec![AofRecord::Command(commands(&[&["PING"]]).remove(0))]
+        );
+        assert_eq!(files[0].1.valid_len(), 14);
+        assert_eq!(files[1].1.next().transpose().unwrap(), None);
+    }
+}
@@ -0,0 +1,317 @@
+//! Reading and writing frames over asynchronous streams, without a codec framework.
+//!
+//! The `futures` module works with the `futures-io` traits and the `tokio` module with the
+//! tokio ones, each behind the feature of the same name. Frames are read through the same
+//! [`ReadBuffer`] as [`crate::reader::RespReader`] uses.
+
+use crate::reader::{end_of_stream, ReadBuffer};
+use crate::{OwnedResp, RespError};
+
+// Records what a read put in the room of `buffer`, returning the frame to give back if the
+// stream ended.
+fn end_read(
+    buffer: &mut ReadBuffer,
+    read: std::io::Result<usize>,
+) -> Result<Option<Option<OwnedResp>>, RespError> {
+    match read? {
+        0 => end_of_stream(buffer.buffered()).map(Some),
+        n => {
+            buffer.advance(n);
+            Ok(None)
+        }
+    }
+}
+
+// The `AsyncWrite` traits of futures-io and tokio share the methods used here, but nothing
+// else, so the function is written once for both.
+macro_rules! write_frame {
+    () => {
+        pub async fn write_frame<W>(writer: &mut W, frame: &Resp<'_>) -> Result<(), RespError>
+        where
+            W: AsyncWrite + Unpin,
+        {
+            let mut output = Vec::new();
+            frame.write_to_writer(&mut output)?;
+            let mut written = 0;
+            while written < output.len() {
+                let n =
+                    poll_fn(|cx| Pin::new(&mut *writer).poll_write(cx, &output[written..])).await?;
+                if n == 0 {
+                    return Err(std::io::Error::from(ErrorKind::WriteZero).into());
+                }
+                written += n;
+            }
+            poll_fn(|cx| Pin::new(&mut *writer).poll_flush(cx)).await?;
+            Ok(())
+        }
+    };
+}
+
+#[cfg(feature = "futures-io")]
+pub mod futures {
+    use super::end_read;
+    use crate::reader::ReadBuffer;
+    use crate::{OwnedResp, Resp, RespError};
+    use futures_io::{AsyncRead, AsyncWrite};
+    use std::future::poll_fn;
+    use std::io::ErrorKind;
+    use std::pin::Pin;
+
+    /// Reads the next frame, using `buffer` to keep whatever was read past it.
+    ///
+    /// The same buffer must be passed to every call on the same stream. Returns `None` when
+    /// the stream ends cleanly between two frames.
+    ///
+    /// This is cancel safe: bytes only count as buffered once a read has completed, so if
+    /// the future is dropped before returning a frame, the next call picks up where it left.
+    pub async fn read_frame<R>(
+        reader: &mut R,
+        buffer: &mut ReadBuffer,
+    ) -> Result<Option<OwnedResp>, RespError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        loop {
+            if let Some(frame) = buffer.take_frame()? {
+                return Ok(Some(frame));
+            }
+            let room = buffer.room();
+            let read = poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, room)).await;
+            if let Some(eof) = end_read(buffer, read)? {
+                return Ok(eof);
+            }
+        }
+    }
+
+    write_frame!();
+}
+
+#[cfg(feature = "tokio")]
+pub mod tokio {
+    use super::end_read;
+    use crate::reader::ReadBuffer;
+    use crate::{OwnedResp, Resp, RespError};
+    use ::tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use std::future::poll_fn;
+    use std::io::ErrorKind;
+    use std::pin::Pin;
+    use std::task::Poll;
+
+    /// Reads the next frame, using `buffer` to keep whatever was read past it.
+    ///
+    /// The same buffer must be passed to every call on the same stream. Returns `None` when
+    /// the stream ends cleanly between two frames.
+    ///
+    /// This is cancel safe: bytes only count as buffered once a read has completed, so if
+    /// the future is dropped before returning a frame, the next call picks up where it left.
+    pub async fn read_frame<R>(
+        reader: &mut R,
+        buffer: &mut ReadBuffer,
+    ) -> Result<Option<OwnedResp>, RespError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        loop {
+            if let Some(frame) = buffer.take_frame()? {
+                return Ok(Some(frame));
+            }
+            let mut read_buf = ReadBuf::new(buffer.room());
+            let read = poll_fn(
+                |cx| match Pin::new(&mut *reader).poll_read(cx, &mut read_buf) {
+                    Poll::Ready(Ok(())) => Poll::Ready(Ok(read_buf.filled().len())),
+                    Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+                    Poll::Pending => Poll::Pending,
+                },
+            )
+            .await;
+            if let Some(eof) = end_read(buffer, read)? {
+                return Ok(eof);
+            }
+        }
+    }
+
+    write_frame!();
+}
+
+#[cfg(test)]
+mod test {
+    use crate::reader::test::Trickle;
+    use crate::reader::ReadBuffer;
+    use std::future::Future;
+    use std::io::Read;
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    // The streams used in these tests never return `Pending`.
+    pub fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[cfg(feature = "futures-io")]
+    impl futures_io::AsyncRead for Trickle<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<std::io::Result<usize>> {
+            Poll::Ready(self.read(buf))
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    impl ::tokio::io::AsyncRead for Trickle<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &mut ::tokio::io::ReadBuf<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            let n = self.read(buf.initialize_unfilled())?;
+            buf.advance(n);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[cfg(feature = "futures-io")]
+    mod futures {
+        use super::*;
+        use crate::async_io::futures::{read_frame, write_frame};
+        use crate::{OwnedResp, Resp, RespError};
+
+        struct Sink(Vec<u8>);
+
+        impl futures_io::AsyncWrite for Sink {
+            fn poll_write(
+                mut self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<std::io::Result<usize>> {
+                // Accept a single byte at a time to exercise partial writes
+                self.0.push(buf[0]);
+                Poll::Ready(Ok(1))
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        #[test]
+        pub fn test_read_frames() {
+            let mut reader = Trickle {
+                input: b"*2\r\n$3\r\nfoo\r\n:1\r\n+OK\r\n",
+                step: 3,
+            };
+            let mut buffer = ReadBuffer::new();
+            let frame = block_on(read_frame(&mut reader, &mut buffer)).unwrap();
+            assert_eq!(
+                frame,
+                Some(OwnedResp::Array(vec![
+                    OwnedResp::BulkString(b"foo".to_vec()),
+                    OwnedResp::Integer(b"1".to_vec()),
+                ]))
+            );
+            let frame = block_on(read_frame(&mut reader, &mut buffer)).unwrap();
+            assert_eq!(frame, Some(OwnedResp::String(b"OK".to_vec())));
+            let frame = block_on(read_frame(&mut reader, &mut buffer)).unwrap();
+            assert_eq!(frame, None);
+        }
+
+        #[test]
+        pub fn test_truncated_frame() {
+            let mut reader = Trickle {
+                input: b"$10\r\nfoo",
+                step: 100,
+            };
+            let mut buffer = ReadBuffer::new();
+            let err = block_on(read_frame(&mut reader, &mut buffer)).unwrap_err();
+            assert!(matches!(err, RespError::Other(_)));
+        }
+
+        #[test]
+        pub fn test_write_frame() {
+            let mut writer = Sink(Vec::new());
+            let frame = Resp::Array(vec![Resp::BulkString(b"GET"), Resp::BulkString(b"k")]);
+            block_on(write_frame(&mut writer, &frame)).unwrap();
+            assert_eq!(writer.0, b"*2\r\n$3\r\nGET\r\n$1\r\nk\r\n".to_vec());
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    mod tokio {
+        use super::*;
+        use crate::async_io::tokio::{read_frame, write_frame};
+        use crate::{OwnedResp, Resp};
+
+        #[test]
+        pub fn test_read_frames() {
+            let mut reader = Trickle {
+                input: b"%1\r\n+a\r\n#t\r\n_\r\n",
+                step: 2,
+            };
+            let mut buffer = ReadBuffer::new();
+            let frame = block_on(read_frame(&mut reader, &mut buffer)).unwrap();
+            assert_eq!(
+                frame,
+                Some(OwnedResp::Map(vec![(
+                    OwnedResp::String(b"a".to_vec()),
+                    OwnedResp::Boolean(true)
+                )]))
+            );
+            let frame = block_on(read_frame(&mut reader, &mut buffer)).unwrap();
+            assert_eq!(frame, Some(OwnedResp::Null));
+            let frame = block_on(read_frame(&mut reader, &mut buffer)).unwrap();
+            assert_eq!(frame, None);
+        }
+
+        // Waits forever once its input is exhausted, like a connection with nothing to read.
+        struct Stalled<'a>(&'a [u8]);
+
+        impl ::tokio::io::AsyncRead for Stalled<'_> {
+            fn poll_read(
+                mut self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+                buf: &mut ::tokio::io::ReadBuf<'_>,
+            ) -> Poll<std::io::Result<()>> {
+                if self.0.is_empty() {
+                    return Poll::Pending;
+                }
+                let n = self.0.read(buf.initialize_unfilled())?;
+                buf.advance(n);
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        #[test]
+        pub fn test_cancelled_read() {
+            let mut buffer = ReadBuffer::new();
+            let mut reader = Stalled(b"$5\r\nhel");
+            {
+                let future = std::pin::pin!(read_frame(&mut reader, &mut buffer));
+                let mut cx = Context::from_waker(Waker::noop());
+                assert!(future.poll(&mut cx).is_pending());
+            }
+            assert_eq!(buffer.buffered(), b"$5\r\nhel");
+            let mut reader = Stalled(b"lo\r\n");
+            let frame = block_on(read_frame(&mut reader, &mut buffer)).unwrap();
+            assert_eq!(frame, Some(OwnedResp::BulkString(b"hello".to_vec())));
+            assert!(buffer.buffered().is_empty());
+        }
+
+        #[test]
+        pub fn test_write_frame() {
+            let mut writer = Vec::new();
+            block_on(write_frame(&mut writer, &Resp::Integer(b"42"))).unwrap();
+            assert_eq!(writer, b":42\r\n".to_vec());
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{parse_everything_until_crlf, RespError, CR, LF};
+
+/// Events yielded by [`BulkDecoder`] while walking through a single bulk string.
+#[derive(Debug, Eq, PartialEq)]
+pub enum BulkEvent<'a> {
+    /// The `$<len>\r\n` header has been consumed.
+    BulkStart { len: usize },
+    /// Part of the payload, never longer than what is left of the bulk string.
+    BulkChunk(&'a [u8]),
+    /// The trailing CRLF has been consumed, the decoder is ready for the next bulk string.
+    BulkEnd,
+    /// A `$-1\r\n` header was consumed, no `BulkEnd` follows.
+    NilBulk,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum State {
+    Header,
+    Payload { remaining: usize },
+    Trailer,
+}
+
+/// Decodes a bulk string without requiring the whole payload to be in memory.
+///
+/// Each call to [`BulkDecoder::next_event`] consumes as much of the input as it can and
+/// returns the leftover, the same way [`crate::parse_resp`] does. The caller is expected to
+/// drop the consumed bytes and feed the decoder more input when it returns
+/// [`RespError::NotEnoughBytes`].
+#[derive(Debug)]
+pub struct BulkDecoder {
+    state: State,
+}
+
+impl Default for BulkDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BulkDecoder {
+    pub fn new() -> Self {
+        Self {
+            state: State::Header,
+        }
+    }
+
+    /// Number of payload bytes that still have to be read before `BulkEnd`.
+    pub fn remaining(&self) -> usize {
+        match self.state {
+            State::Payload { remaining } => remaining,
+            _ => 0,
+        }
+    }
+
+    pub fn next_event<'a>(
+        &mut self,
+        input: &'a [u8],
+    ) -> Result<(BulkEvent<'a>, &'a [u8]), RespError> {
+        match self.state {
+            State::Header => {
+                if input.is_empty() {
+                    return Err(RespError::NotEnoughBytes);
+                } else if input[0] != b'$' {
+                    return Err(RespError::IncorrectFormat);
+                }
+                let (size_str, leftover) = parse_everything_until_crlf(&input[1..])?;
+                let size = std::str::from_utf8(size_str)?.parse::<i64>()?;
+                if size < 0 {
+                    Ok((BulkEvent::NilBulk, leftover))
+                } else {
+                    let len = size as usize;
+                    self.state = if len == 0 {
+                        State::Trailer
+                    } else {
+                        State::Payload { remaining: len }
+                    };
+                    Ok((BulkEvent::BulkStart { len }, leftover))
+                }
+            }
+            State::Payload { remaining } => {
+                if input.is_empty() {
+                    return Err(RespError::NotEnoughBytes);
+                }
+                let taken = remaining.min(input.len());
+                self.state = if taken == remaining {
+                    State::Trailer
+                } else {
+                    State::Payload {
+                        remaining: remaining - taken,
+                    }
+                };
+                Ok((BulkEvent::BulkChunk(&input[..taken]), &input[taken..]))
+            }
+            State::Trailer => match input {
+                [CR, LF, leftover @ ..] => {
+                    self.state = State::Header;
+                    Ok((BulkEvent::BulkEnd, leftover))
+                }
+                [] | [CR] => Err(RespError::NotEnoughBytes),
+                _ => Err(RespError::IncorrectFormat),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_bulk_in_one_go() {
+        let mut decoder = BulkDecoder::new();
+        let input = b"$6\r\nfoobar\r\n+OK\r\n";
+        let (event, left) = decoder.next_event(input).unwrap();
+        assert_eq!(event, BulkEvent::BulkStart { len: 6 });
+        let (event, left) = decoder.next_event(left).unwrap();
+        assert_eq!(event, BulkEvent::BulkChunk(b"foobar"));
+        let (event, left) = decoder.next_event(left).unwrap();
+        assert_eq!(event, BulkEvent::BulkEnd);
+        assert_eq!(left, b"+OK\r\n");
+    }
+
+    #[test]
+    pub fn test_bulk_split_across_reads() {
+        let mut decoder = BulkDecoder::new();
+        let (event, left) = decoder.next_event(b"$10\r\nhel").unwrap();
+        assert_eq!(event, BulkEvent::BulkStart { len: 10 });
+        let (event, left) = decoder.next_event(left).unwrap();
+        assert_eq!(event, BulkEvent::BulkChunk(b"hel"));
+        assert!(left.is_empty());
+        assert_eq!(decoder.remaining(), 7);
+        let err = decoder.next_event(left).unwrap_err();
+        assert!(matches!(err, RespError::NotEnoughBytes));
+        let (event, left) = decoder.next_event(b"lo worl\r").unwrap();
+        assert_eq!(event, BulkEvent::BulkChunk(b"lo worl"));
+        assert_eq!(left, b"\r");
+        let err = decoder.next_event(left).unwrap_err();
+        assert!(matches!(err, RespError::NotEnoughBytes));
+        let (event, left) = decoder.next_event(b"\r\n").unwrap();
+        assert_eq!(event, BulkEvent::BulkEnd);
+        assert!(left.is_empty());
+    }
+
+    #[test]
+    pub fn test_bulk_edge_cases() {
+        let mut decoder = BulkDecoder::new();
+        let (event, left) = decoder.next_event(b"$-1\r\n").unwrap();
+        assert_eq!(event, BulkEvent::NilBulk);
+        assert!(left.is_empty());
+        let (event, left) = decoder.next_event(b"$0\r\n\r\n").unwrap();
+        assert_eq!(event, BulkEvent::BulkStart { len: 0 });
+        let (event, left) = decoder.next_event(left).unwrap();
+        assert_eq!(event, BulkEvent::BulkEnd);
+        assert!(left.is_empty());
+        let err = decoder.next_event(b"$3").unwrap_err();
+        assert!(matches!(err, RespError::NotEnoughBytes));
+        let err = decoder.next_event(b"+OK\r\n").unwrap_err();
+        assert!(matches!(err, RespError::IncorrectFormat));
+        let (_, left) = decoder.next_event(b"$1\r\nab\r\n").unwrap();
+        let (_, left) = decoder.next_event(left).unwrap();
+        let err = decoder.next_event(left).unwrap_err();
+        assert!(matches!(err, RespError::IncorrectFormat));
+    }
+}
@@ -0,0 +1,510 @@
+//! A minimal blocking client, mostly meant as a reference for how the parser and the encoder
+//! fit together.
+//!
+//! Replies are returned as they were received, error replies included. Push frames received
+//! while waiting for a reply are kept aside and can be collected with [`Client::take_pushes`].
+//!
+//! The subscription commands are answered with one confirmation per channel or pattern, which
+//! are pushes in RESP3. They are collected and returned together as an array. Unsubscribing
+//! from everything is rejected, since the number of confirmations is not known upfront.
+//!
+//! In RESP2, messages are arrays like any other reply. While the connection is subscribed,
+//! going by the counts carried by the confirmations, they are kept aside as well.
+
+use crate::hello::{is_noproto, is_unknown_command, Hello};
+use crate::pubsub::{expected_confirmations, Confirmations, PubSubMessage};
+use crate::push::{is_confirmation, is_push};
+use crate::reader::RespReader;
+use crate::transaction::Transaction;
+use crate::{OwnedResp, Resp, RespError};
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+
+#[derive(Debug)]
+pub struct Client<S> {
+    reader: RespReader<S>,
+    pushes: VecDeque<OwnedResp>,
+    // From the last confirmations, shard channels are counted apart
+    subscriptions: i64,
+    shard_subscriptions: i64,
+}
+
+/// Encodes a command the way clients send it, as an array of bulk strings.
+pub fn encode_command<A, W>(args: &[A], writer: &mut W) -> Result<(), RespError>
+where
+    A: AsRef<[u8]>,
+    W: Write,
+{
+    let args = args.iter().map(|a| Resp::BulkString(a.as_ref())).collect();
+    Resp::Array(args).write_to_writer(writer)
+}
+
+// Turns an error reply to a handshake command into an error.
+fn check_reply(reply: OwnedResp) -> Result<OwnedResp, RespError> {
+    match reply {
+        OwnedResp::Error(e) | OwnedResp::BlobError(e) => {
+            Err(RespError::Other(String::from_utf8_lossy(&e).into()))
+        }
+        reply => Ok(reply),
+    }
+}
+
+impl Client<TcpStream> {
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> Result<Self, RespError> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Self::new(stream))
+    }
+}
+
+#[cfg(unix)]
+impl Client<std::os::unix::net::UnixStream> {
+    pub fn connect_unix<P: AsRef<std::path::Path>>(path: P) -> Result<Self, RespError> {
+        let stream = std::os::unix::net::UnixStream::connect(path)?;
+        Ok(Self::new(stream))
+    }
+}
+
+impl<S: Read + Write> Client<S> {
+    /// Wraps an already connected stream.
+    pub fn new(stream: S) -> Self {
+        Self {
+            reader: RespReader::new(stream),
+            pushes: VecDeque::new(),
+            subscriptions: 0,
+            shard_subscriptions: 0,
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        self.reader.get_ref()
+    }
+
+    /// Sends `AUTH`, with a username when using ACLs.
+    pub fn auth(&mut self, username: Option<&[u8]>, password: &[u8]) -> Result<(), RespError> {
+        let reply = match username {
+            Some(username) => self.send(&[&b"AUTH"[..], username, password])?,
+            None => self.send(&[&b"AUTH"[..], password])?,
+        };
+        check_reply(reply).map(|_| ())
+    }
+
+    /// Sends `HELLO` to switch to the given protocol version, returning the server properties.
+    pub fn hello(&mut self, protover: u8) -> Result<OwnedResp, RespError> {
+        let protover = protover.to_string();
+        let reply = self.send(&[&b"HELLO"[..], protover.as_bytes()])?;
+        check_reply(reply)
+    }
+
+    /// Sends a full `HELLO`, retrying with RESP2 if the server does not support the requested
+    /// version. The reply can be read with [`crate::hello::HelloResponse`].
+    ///
+    /// Servers older than 6.0 do not know `HELLO` and only speak RESP2. The connection is then
+    /// authenticated and named with `AUTH` and `CLIENT SETNAME` instead, and `None` is returned
+    /// since there are no server properties to return. These servers have no ACL users, so
+    /// only the password is sent.
+    pub fn handshake(&mut self, hello: &Hello) -> Result<Option<OwnedResp>, RespError> {
+        let mut output = Vec::new();
+        hello.write_to_writer(&mut output)?;
+        self.write(&output)?;
+        let reply = self.read_reply()?;
+        if hello.protover > 2 && is_noproto(&reply.as_resp()) {
+            return self.handshake(&hello.with_protover(2));
+        }
+        if is_unknown_command(&reply.as_resp()) {
+            if let Some((_, password)) = &hello.auth {
+                self.auth(None, password)?;
+            }
+            if let Some(name) = &hello.setname {
+                check_reply(self.send(&[&b"CLIENT"[..], b"SETNAME", name])?)?;
+            }
+            return Ok(None);
+        }
+        check_reply(reply).map(Some)
+    }
+
+    /// Sends a single command and waits for its reply.
+    pub fn send<A: AsRef<[u8]>>(&mut self, args: &[A]) -> Result<OwnedResp, RespError> {
+        let confirmations = expected_confirmations(args);
+        check_confirmations(confirmations)?;
+        let mut output = Vec::new();
+        encode_command(args, &mut output)?;
+        self.write(&output)?;
+        self.read_replies(confirmations)
+    }
+
+    /// Starts a batch of commands sent in a single write.
+    pub fn pipeline(&mut self) -> Pipeline<'_, S> {
+        Pipeline {
+            client: self,
+            output: Vec::new(),
+            confirmations: Vec::new(),
+        }
+    }
+
+    /// Sends a whole transaction and returns the replies from `MULTI` to `EXEC`, to be passed
+    /// to [`Transaction::interpret`].
+    pub fn transaction(&mut self, tx: &Transaction) -> Result<Vec<OwnedResp>, RespError> {
+        let mut output = Vec::new();
+        tx.write_to_writer(&mut output)?;
+        self.write(&output)?;
+        (0..tx.len() + 2).map(|_| self.read_reply()).collect()
+    }
+
+    /// Push frames received so far, oldest first.
+    pub fn take_pushes(&mut self) -> Vec<OwnedResp> {
+        self.pushes.drain(..).collect()
+    }
+
+    fn write(&mut self, output: &[u8]) -> Result<(), RespError> {
+        let stream = self.reader.get_mut();
+        stream.write_all(output)?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    fn read_frame(&mut self) -> Result<OwnedResp, RespError> {
+        let frame = match self.reader.read_frame()? {
+            Some(frame) => frame,
+            None => return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into()),
+        };
+        match PubSubMessage::try_from(&frame.as_resp()) {
+            Ok(
+                PubSubMessage::Subscribe { count, .. }
+                | PubSubMessage::Unsubscribe { count, .. }
+                | PubSubMessage::PSubscribe { count, .. }
+                | PubSubMessage::PUnsubscribe { count, .. },
+            ) => self.subscriptions = count,
+            Ok(
+                PubSubMessage::SSubscribe { count, .. } | PubSubMessage::SUnsubscribe { count, .. },
+            ) => self.shard_subscriptions = count,
+            _ => (),
+        }
+        Ok(frame)
+    }
+
+    // Whether the frame is not a reply: a push, or a RESP2 message or confirmation while
+    // subscribed. A PING is still answered with a reply then, even if it looks like a message.
+    fn is_message(&self, frame: &Resp) -> bool {
+        if is_push(frame) {
+            return true;
+        }
+        let subscribed = self.subscriptions > 0 || self.shard_subscriptions > 0;
+        subscribed
+            && PubSubMessage::try_from(frame).is_ok_and(|m| !matches!(m, PubSubMessage::Pong(_)))
+    }
+
+    fn read_reply(&mut self) -> Result<OwnedResp, RespError> {
+        loop {
+            let frame = self.read_frame()?;
+            if self.is_message(&frame.as_resp()) {
+                self.pushes.push_back(frame);
+            } else {
+                return Ok(frame);
+            }
+        }
+    }
+
+    // Reads the confirmations of a subscription command, keeping aside the messages that
+    // arrive in between. An error reply stands for all of them.
+    fn read_replies(
+        &mut self,
+        confirmations: Option<Confirmations>,
+    ) -> Result<OwnedResp, RespError> {
+        let count = match confirmations {
+            Some(Confirmations::Exactly(count)) => count,
+            _ => return self.read_reply(),
+        };
+        let mut replies = Vec::with_capacity(count);
+        while replies.len() < count {
+            let frame = self.read_frame()?;
+            let (confirmation, message) = {
+                let resp = frame.as_resp();
+                let message = is_push(&resp) || PubSubMessage::try_from(&resp).is_ok();
+                (is_confirmation(&resp), message)
+            };
+            if confirmation {
+                replies.push(frame);
+            } else if message {
+                self.pushes.push_back(frame);
+            } else {
+                return Ok(frame);
+            }
+        }
+        Ok(OwnedResp::Array(replies))
+    }
+}
+
+fn check_confirmations(confirmations: Option<Confirmations>) -> Result<(), RespError> {
+    match confirmations {
+        Some(Confirmations::UntilZeroCount) => Err(RespError::Other(
+            "cannot wait for the confirmations of an unsubscribe from everything".into(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Commands queued with [`Pipeline::cmd`], sent together by [`Pipeline::execute`].
+#[derive(Debug)]
+pub struct Pipeline<'c, S> {
+    client: &'c mut Client<S>,
+    output: Vec<u8>,
+    // For each command, see `expected_confirmations`
+    confirmations: Vec<Option<Confirmations>>,
+}
+
+impl<S: Read + Write> Pipeline<'_, S> {
+    pub fn cmd<A: AsRef<[u8]>>(mut self, args: &[A]) -> Self {
+        // Writing to a Vec cannot fail
+        encode_command(args, &mut self.output).unwrap();
+        self.confirmations.push(expected_confirmations(args));
+        self
+    }
+
+    /// Sends every command and returns their replies, in order.
+    pub fn execute(self) -> Result<Vec<OwnedResp>, RespError> {
+        if self.confirmations.is_empty() {
+            return Ok(Vec::new());
+        }
+        for confirmations in &self.confirmations {
+            check_confirmations(*confirmations)?;
+        }
+        self.client.write(&self.output)?;
+        self.confirmations
+            .iter()
+            .map(|confirmations| self.client.read_replies(*confirmations))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hello::HelloResponse;
+    use crate::transaction::TransactionOutcome;
+    use std::net::TcpListener;
+    use std::thread;
+
+    // Accepts a single connection and answers every command with `reply`.
+    fn fake_server<F>(reply: F) -> std::net::SocketAddr
+    where
+        F: Fn(&[Vec<u8>]) -> Vec<u8> + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let reader = RespReader::new(stream.try_clone().unwrap());
+            for frame in reader {
+                let args = match frame.unwrap() {
+                    OwnedResp::Array(args) => args
+                        .into_iter()
+                        .map(|a| match a {
+                            OwnedResp::BulkString(a) => a,
+                            a => panic!("unexpected argument {:?}", a),
+                        })
+                        .collect::<Vec<_>>(),
+                    frame => panic!("unexpected frame {:?}", frame),
+                };
+                stream.write_all(&reply(&args)).unwrap();
+            }
+        });
+        addr
+    }
+
+    #[test]
+    pub fn test_send() {
+        let addr = fake_server(|args| match args[0].as_slice() {
+            b"AUTH" if args.last().unwrap() == b"secret" => b"+OK\r\n".to_vec(),
+            b"AUTH" => b"-WRONGPASS invalid password\r\n".to_vec(),
+            b"HELLO" => b"%1\r\n+proto\r\n:3\r\n".to_vec(),
+            b"GET" => b">2\r\n+invalidate\r\n*1\r\n+k\r\n$3\r\nbar\r\n".to_vec(),
+            _ => b"-ERR unknown command\r\n".to_vec(),
+        });
+        let mut client = Client::connect(addr).unwrap();
+        assert!(client.auth(None, b"nope").is_err());
+        client.auth(Some(b"default"), b"secret").unwrap();
+        assert_eq!(
+            client.hello(3).unwrap(),
+            OwnedResp::Map(vec![(
+                OwnedResp::String(b"proto".to_vec()),
+                OwnedResp::Integer(b"3".to_vec())
+            )])
+        );
+        assert_eq!(
+            client.send(&["GET", "k"]).unwrap(),
+            OwnedResp::BulkString(b"bar".to_vec())
+        );
+        assert_eq!(client.take_pushes().len(), 1);
+        assert_eq!(
+            client.send(&["NOPE"]).unwrap(),
+            OwnedResp::Error(b"ERR unknown command".to_vec())
+        );
+    }
+
+    #[test]
+    pub fn test_subscribe() {
+        let addr = fake_server(|args| {
+            let mut output = Vec::new();
+            if args[0] == b"SUBSCRIBE" {
+                output.extend_from_slice(b">3\r\n$7\r\nmessage\r\n$3\r\nold\r\n$2\r\nhi\r\n");
+            }
+            for (i, channel) in args[1..].iter().enumerate() {
+                let kind = String::from_utf8_lossy(&args[0]).to_lowercase();
+                Resp::Push(vec![
+                    Resp::BulkString(kind.as_bytes()),
+                    Resp::BulkString(channel),
+                    Resp::Integer(format!("{}", i + 1).as_bytes()),
+                ])
+                .write_to_writer(&mut output)
+                .unwrap();
+            }
+            if args[0] == b"PING" {
+                output.extend_from_slice(b"+PONG\r\n");
+            }
+            output
+        });
+        let mut client = Client::connect(addr).unwrap();
+        let reply = client.send(&["SUBSCRIBE", "a", "b"]).unwrap();
+        let OwnedResp::Array(confirmations) = reply else {
+            panic!("{:?}", reply)
+        };
+        assert_eq!(confirmations.len(), 2);
+        assert_eq!(
+            PubSubMessage::try_from(&confirmations[1].as_resp()).unwrap(),
+            PubSubMessage::Subscribe {
+                channel: b"b",
+                count: 2
+            }
+        );
+        assert_eq!(client.take_pushes().len(), 1);
+        assert_eq!(
+            client.send(&["PING"]).unwrap(),
+            OwnedResp::String(b"PONG".to_vec())
+        );
+        assert!(client.send(&["UNSUBSCRIBE"]).is_err());
+        let replies = client
+            .pipeline()
+            .cmd(&["UNSUBSCRIBE", "a"])
+            .cmd(&["PING"])
+            .execute()
+            .unwrap();
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[1], OwnedResp::String(b"PONG".to_vec()));
+    }
+
+    #[test]
+    pub fn test_subscribe_resp2() {
+        let addr = fake_server(|args| match args[0].as_slice() {
+            b"SUBSCRIBE" => b"*3\r\n$9\r\nsubscribe\r\n$1\r\na\r\n:1\r\n".to_vec(),
+            b"UNSUBSCRIBE" => b"*3\r\n$11\r\nunsubscribe\r\n$1\r\na\r\n:0\r\n".to_vec(),
+            b"PING" => {
+                b"*3\r\n$7\r\nmessage\r\n$1\r\na\r\n$2\r\nhi\r\n*2\r\n$4\r\npong\r\n$0\r\n\r\n"
+                    .to_vec()
+            }
+            _ => b"*3\r\n$7\r\nmessage\r\n$1\r\na\r\n$2\r\nhi\r\n".to_vec(),
+        });
+        let mut client = Client::connect(addr).unwrap();
+        let message = OwnedResp::Array(vec![
+            OwnedResp::BulkString(b"message".to_vec()),
+            OwnedResp::BulkString(b"a".to_vec()),
+            OwnedResp::BulkString(b"hi".to_vec()),
+        ]);
+        client.send(&["SUBSCRIBE", "a"]).unwrap();
+        let reply = client.send(&["PING"]).unwrap();
+        assert_eq!(
+            PubSubMessage::try_from(&reply.as_resp()).unwrap(),
+            PubSubMessage::Pong(b"")
+        );
+        assert_eq!(client.take_pushes(), vec![message.clone()]);
+        client.send(&["UNSUBSCRIBE", "a"]).unwrap();
+        // Once unsubscribed, the same array is an ordinary reply
+        assert_eq!(client.send(&["LRANGE", "l", "0", "-1"]).unwrap(), message);
+        assert!(client.take_pushes().is_empty());
+    }
+
+    #[test]
+    pub fn test_handshake_fallback() {
+        let addr = fake_server(|args| {
+            match args[1].as_slice() {
+            b"3" => b"-NOPROTO unsupported protocol version\r\n".to_vec(),
+            _ if args.len() == 5 => b"*6\r\n$6\r\nserver\r\n$5\r\nredis\r\n$7\r\nversion\r\n$5\r\n5.0.0\r\n$5\r\nproto\r\n:2\r\n".to_vec(),
+            _ => b"-ERR wrong number of arguments\r\n".to_vec(),
+        }
+        });
+        let mut client = Client::connect(addr).unwrap();
+        let reply = client
+            .handshake(&Hello::new(3).auth(b"user", b"pass"))
+            .unwrap()
+            .unwrap();
+        let hello = HelloResponse::try_from(&reply.as_resp()).unwrap();
+        assert_eq!(hello.proto, 2);
+        assert_eq!(hello.version, b"5.0.0");
+    }
+
+    #[test]
+    pub fn test_handshake_without_hello() {
+        let addr = fake_server(|args| {
+            let args: Vec<_> = args.iter().map(Vec::as_slice).collect();
+            match args.as_slice() {
+                [b"AUTH", b"pass"] | [b"CLIENT", b"SETNAME", b"app"] => b"+OK\r\n".to_vec(),
+                [b"HELLO", ..] => b"-ERR unknown command 'HELLO'\r\n".to_vec(),
+                _ => b"-ERR syntax error\r\n".to_vec(),
+            }
+        });
+        let mut client = Client::connect(addr).unwrap();
+        let hello = Hello::new(3).auth(b"default", b"pass").setname(b"app");
+        assert_eq!(client.handshake(&hello).unwrap(), None);
+        let hello = Hello::new(3).setname(b"other");
+        assert!(client.handshake(&hello).is_err());
+    }
+
+    #[test]
+    pub fn test_pipeline() {
+        let addr = fake_server(|args| {
+            let mut output = Vec::new();
+            Resp::BulkString(&args[1])
+                .write_to_writer(&mut output)
+                .unwrap();
+            output
+        });
+        let mut client = Client::connect(addr).unwrap();
+        let replies = client
+            .pipeline()
+            .cmd(&["ECHO", "a"])
+            .cmd(&["ECHO", "b"])
+            .cmd(&["ECHO", "c"])
+            .execute()
+            .unwrap();
+        assert_eq!(
+            replies,
+            vec![
+                OwnedResp::BulkString(b"a".to_vec()),
+                OwnedResp::BulkString(b"b".to_vec()),
+                OwnedResp::BulkString(b"c".to_vec()),
+            ]
+        );
+        assert_eq!(client.pipeline().execute().unwrap(), vec![]);
+    }
+
+    #[test]
+    pub fn test_transaction() {
+        let addr = fake_server(|args| match args[0].as_slice() {
+            b"MULTI" => b"+OK\r\n".to_vec(),
+            b"EXEC" => b"*1\r\n:1\r\n".to_vec(),
+            _ => b"+QUEUED\r\n".to_vec(),
+        });
+        let mut client = Client::connect(addr).unwrap();
+        let mut tx = Transaction::new();
+        tx.cmd(&["INCR", "k"]);
+        let replies = client.transaction(&tx).unwrap();
+        let outcome = tx
+            .interpret(replies.iter().map(OwnedResp::as_resp))
+            .unwrap();
+        assert_eq!(
+            outcome,
+            TransactionOutcome::Executed(vec![Ok(Resp::Integer(b"1"))])
+        );
+    }
+}
@@ -0,0 +1,427 @@
+//! Which node of a Redis Cluster each request goes to.
+//!
+//! A [`SlotMap`] is built from a `CLUSTER SLOTS` or `CLUSTER SHARDS` reply and kept up to date
+//! with the `MOVED` redirections seen afterwards.
+
+use crate::error::{RedisError, RedisErrorKind};
+use crate::{Resp, RespError};
+
+pub const SLOT_COUNT: u16 = 16384;
+
+/// A primary node, by the address clients connect to.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Node {
+    pub host: String,
+    pub port: u16,
+}
+
+// CRC16-CCITT (XMODEM), as used by Redis Cluster
+fn crc16(input: &[u8]) -> u16 {
+    let mut crc: u16 = 0;
+    for &byte in input {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// The slot of a key, only hashing what is between the first `{` and the next `}` when that is
+/// not empty, so that related keys can be kept together.
+pub fn key_slot(key: &[u8]) -> u16 {
+    let tag = key.iter().position(|&c| c == b'{').and_then(|open| {
+        let rest = &key[open + 1..];
+        match rest.iter().position(|&c| c == b'}') {
+            Some(close) if close > 0 => Some(&rest[..close]),
+            _ => None,
+        }
+    });
+    crc16(tag.unwrap_or(key)) % SLOT_COUNT
+}
+
+// Keyless commands, which can go to any node
+const KEYLESS_COMMANDS: &[&[u8]] = &[
+    b"AUTH",
+    b"CLIENT",
+    b"CLUSTER",
+    b"COMMAND",
+    b"CONFIG",
+    b"DBSIZE",
+    b"DISCARD",
+    b"ECHO",
+    b"EXEC",
+    b"FLUSHALL",
+    b"FLUSHDB",
+    b"FUNCTION",
+    b"HELLO",
+    b"INFO",
+    b"MULTI",
+    b"PING",
+    b"PUBLISH",
+    b"QUIT",
+    b"READONLY",
+    b"READWRITE",
+    b"SCRIPT",
+    b"SELECT",
+    b"TIME",
+    b"UNWATCH",
+];
+
+// Commands whose arguments are all keys
+const ALL_KEYS_COMMANDS: &[&[u8]] = &[
+    b"DEL",
+    b"EXISTS",
+    b"MGET",
+    b"PFCOUNT",
+    b"PFMERGE",
+    b"SDIFF",
+    b"SINTER",
+    b"SSUBSCRIBE",
+    b"SUNION",
+    b"SUNSUBSCRIBE",
+    b"TOUCH",
+    b"UNLINK",
+    b"WATCH",
+];
+
+/// The keys of a command, for the commands whose keys are not simply the first argument.
+pub fn command_keys<A: AsRef<[u8]>>(command: &[A]) -> Vec<&[u8]> {
+    let name = match command.first() {
+        Some(name) => name.as_ref().to_ascii_uppercase(),
+        None => return Vec::new(),
+    };
+    let args: Vec<&[u8]> = command[1..].iter().map(AsRef::as_ref).collect();
+    match name.as_slice() {
+        name if KEYLESS_COMMANDS.contains(&name) => Vec::new(),
+        name if ALL_KEYS_COMMANDS.contains(&name) => args,
+        b"MSET" | b"MSETNX" => args.into_iter().step_by(2).collect(),
+        b"EVAL" | b"EVALSHA" | b"EVAL_RO" | b"EVALSHA_RO" | b"FCALL" | b"FCALL_RO" => {
+            let count = args
+                .get(1)
+                .and_then(|n| std::str::from_utf8(n).ok())
+                .and_then(|n| n.parse::<usize>().ok())
+                .unwrap_or(0);
+            args.into_iter().skip(2).take(count).collect()
+        }
+        b"XREAD" | b"XREADGROUP" => {
+            let streams = args.iter().position(|a| a.eq_ignore_ascii_case(b"STREAMS"));
+            match streams {
+                Some(index) => {
+                    let rest = &args[index + 1..];
+                    rest[..rest.len() / 2].to_vec()
+                }
+                None => Vec::new(),
+            }
+        }
+        _ => args.into_iter().take(1).collect(),
+    }
+}
+
+/// The slot a command must be sent to, or `None` if it has no keys.
+///
+/// Fails like Redis would with `CROSSSLOT` if the keys are in different slots.
+pub fn command_slot<A: AsRef<[u8]>>(command: &[A]) -> Result<Option<u16>, RespError> {
+    let mut slots = command_keys(command).into_iter().map(key_slot);
+    let slot = match slots.next() {
+        Some(slot) => slot,
+        None => return Ok(None),
+    };
+    if slots.any(|other| other != slot) {
+        return Err(RespError::Other(
+            "keys in request don't hash to the same slot".into(),
+        ));
+    }
+    Ok(Some(slot))
+}
+
+/// The primary node owning each slot.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SlotMap {
+    nodes: Vec<Node>,
+    // Index in `nodes` of the owner of each slot
+    slots: Vec<Option<usize>>,
+}
+
+impl Default for SlotMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn bytes<'a>(resp: &Resp<'a>) -> Result<&'a [u8], RespError> {
+    resp.as_bytes().ok_or(RespError::IncorrectFormat)
+}
+
+fn integer(resp: &Resp) -> Result<i64, RespError> {
+    resp.as_integer().ok_or(RespError::IncorrectFormat)
+}
+
+fn port(resp: &Resp) -> Result<u16, RespError> {
+    u16::try_from(integer(resp)?).map_err(|_| RespError::IncorrectFormat)
+}
+
+fn slot(resp: &Resp) -> Result<u16, RespError> {
+    match u16::try_from(integer(resp)?) {
+        Ok(slot) if slot < SLOT_COUNT => Ok(slot),
+        _ => Err(RespError::IncorrectFormat),
+    }
+}
+
+// The fields of a map, sent as a flat array over RESP2.
+fn fields<'r, 'a>(resp: &'r Resp<'a>) -> Result<Vec<(&'a [u8], &'r Resp<'a>)>, RespError> {
+    match resp {
+        Resp::Map(m) => m.iter().map(|(k, v)| Ok((bytes(k)?, v))).collect(),
+        Resp::Array(a) if a.len() % 2 == 0 => a
+            .chunks(2)
+            .map(|pair| Ok((bytes(&pair[0])?, &pair[1])))
+            .collect(),
+        _ => Err(RespError::IncorrectFormat),
+    }
+}
+
+fn host(host: &[u8]) -> Result<String, RespError> {
+    Ok(std::str::from_utf8(host)?.to_string())
+}
+
+impl SlotMap {
+    /// A map where no slot has an owner yet.
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            slots: vec![None; SLOT_COUNT as usize],
+        }
+    }
+
+    /// Reads the primaries out of a `CLUSTER SLOTS` reply.
+    pub fn from_cluster_slots(reply: &Resp) -> Result<Self, RespError> {
+        let ranges = match reply {
+            Resp::Array(a) => a,
+            _ => return Err(RespError::IncorrectFormat),
+        };
+        let mut map = SlotMap::new();
+        for range in ranges {
+            match range {
+                Resp::Array(a) if a.len() >= 3 => match &a[2] {
+                    Resp::Array(primary) if primary.len() >= 2 => {
+                        let node = Node {
+                            host: host(bytes(&primary[0])?)?,
+                            port: port(&primary[1])?,
+                        };
+                        map.assign(slot(&a[0])?, slot(&a[1])?, node);
+                    }
+                    _ => return Err(RespError::IncorrectFormat),
+                },
+                _ => return Err(RespError::IncorrectFormat),
+            }
+        }
+        Ok(map)
+    }
+
+    /// Reads the primaries out of a `CLUSTER SHARDS` reply, over RESP2 or RESP3.
+    pub fn from_cluster_shards(reply: &Resp) -> Result<Self, RespError> {
+        let shards = match reply {
+            Resp::Array(a) => a,
+            _ => return Err(RespError::IncorrectFormat),
+        };
+        let mut map = SlotMap::new();
+        for shard in shards {
+            let (mut ranges, mut primary) = (None, None);
+            for (key, value) in fields(shard)? {
+                match (key, value) {
+                    (b"slots", Resp::Array(a)) => ranges = Some(a),
+                    (b"nodes", Resp::Array(nodes)) => {
+                        for node in nodes {
+                            if let Some(node) = shard_primary(node)? {
+                                primary = Some(node);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if let (Some(ranges), Some(primary)) = (ranges, primary) {
+                if ranges.len() % 2 != 0 {
+                    return Err(RespError::IncorrectFormat);
+                }
+                for range in ranges.chunks(2) {
+                    map.assign(slot(&range[0])?, slot(&range[1])?, primary.clone());
+                }
+            }
+        }
+        Ok(map)
+    }
+
+    fn assign(&mut self, start: u16, end: u16, node: Node) {
+        let index = match self.nodes.iter().position(|n| n == &node) {
+            Some(index) => index,
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        for slot in start..=end {
+            self.slots[slot as usize] = Some(index);
+        }
+    }
+
+    /// Every node that owned a slot at some point, including those that lost all their slots
+    /// since.
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn node_for_slot(&self, slot: u16) -> Option<&Node> {
+        let index = (*self.slots.get(slot as usize)?)?;
+        Some(&self.nodes[index])
+    }
+
+    pub fn node_for_key(&self, key: &[u8]) -> Option<&Node> {
+        self.node_for_slot(key_slot(key))
+    }
+
+    /// The node to send a request to, or `None` if it has no keys and can go to any node.
+    ///
+    /// Fails if the keys are in different slots or if the slot has no known owner.
+    pub fn route<A: AsRef<[u8]>>(&self, command: &[A]) -> Result<Option<&Node>, RespError> {
+        match command_slot(command)? {
+            Some(slot) => match self.node_for_slot(slot) {
+                Some(node) => Ok(Some(node)),
+                None => Err(RespError::Other(
+                    format!("slot {} is not served by any node", slot).into(),
+                )),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Moves the slot of a `MOVED` redirection to its new owner, returning whether the reply
+    /// was one.
+    ///
+    /// A redirection without a host points to the host the request was sent to, which is
+    /// assumed to be the previous owner of the slot.
+    pub fn update(&mut self, reply: &Resp) -> bool {
+        let redirection = match RedisError::try_from(reply) {
+            Ok(RedisError {
+                kind: RedisErrorKind::Moved(redirection),
+                ..
+            }) => redirection,
+            _ => return false,
+        };
+        let host = match (redirection.host, self.node_for_slot(redirection.slot)) {
+            ("", Some(previous)) => previous.host.clone(),
+            (host, _) => host.to_string(),
+        };
+        let node = Node {
+            host,
+            port: redirection.port,
+        };
+        self.assign(redirection.slot, redirection.slot, node);
+        true
+    }
+}
+
+// The address of a node from a `CLUSTER SHARDS` reply, if it is the primary.
+fn shard_primary(node: &Resp) -> Result<Option<Node>, RespError> {
+    let (mut role, mut address, mut port_number) = (None, None, None);
+    for (key, value) in fields(node)? {
+        match key {
+            b"role" => role = Some(bytes(value)?),
+            // `endpoint` is what the node advertises to clients, `ip` is the fallback
+            b"endpoint" => address = Some(bytes(value)?),
+            b"ip" if address.is_none() => address = Some(bytes(value)?),
+            b"port" => port_number = Some(port(value)?),
+            _ => {}
+        }
+    }
+    match (role, address, port_number) {
+        (Some(b"master" | b"primary"), Some(address), Some(port)) => Ok(Some(Node {
+            host: host(address)?,
+            port,
+        })),
+        (Some(_), Some(_), Some(_)) => Ok(None),
+        _ => Err(RespError::IncorrectFormat),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_resp;
+
+    fn node(port: u16) -> Node {
+        Node {
+            host: "127.0.0.1".to_string(),
+            port,
+        }
+    }
+
+    #[test]
+    pub fn test_key_slot() {
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+        assert_eq!(key_slot(b"foo"), 12182);
+        assert_eq!(key_slot(b"{user1000}.following"), key_slot(b"user1000"));
+        assert_eq!(key_slot(b"foo{}{bar}"), key_slot(b"foo{}{bar}"));
+        assert_ne!(key_slot(b"foo{}{bar}"), key_slot(b"bar"));
+        assert_eq!(key_slot(b"foo{{bar}}zap"), key_slot(b"{bar"));
+    }
+
+    #[test]
+    pub fn test_command_slot() {
+        assert_eq!(command_slot(&["GET", "foo"]).unwrap(), Some(12182));
+        assert_eq!(command_slot(&["PING"]).unwrap(), None);
+        assert_eq!(
+            command_slot(&["MSET", "{a}1", "x", "{a}2", "y"]).unwrap(),
+            Some(key_slot(b"a"))
+        );
+        assert!(command_slot(&["MGET", "a", "b"]).is_err());
+        assert_eq!(
+            command_keys(&["EVAL", "return 1", "1", "k", "arg"]),
+            vec![&b"k"[..]]
+        );
+        assert_eq!(
+            command_keys(&["XREAD", "COUNT", "2", "STREAMS", "s1", "s2", "0", "0"]),
+            vec![&b"s1"[..], &b"s2"[..]]
+        );
+    }
+
+    #[test]
+    pub fn test_cluster_slots() {
+        let input = b"*2\r\n*3\r\n:0\r\n:5460\r\n*3\r\n$9\r\n127.0.0.1\r\n:30001\r\n$4\r\nid-1\r\n*4\r\n:5461\r\n:16383\r\n*3\r\n$9\r\n127.0.0.1\r\n:30002\r\n$4\r\nid-2\r\n*3\r\n$9\r\n127.0.0.1\r\n:30003\r\n$4\r\nid-3\r\n";
+        let (reply, _) = parse_resp(input).unwrap();
+        let mut map = SlotMap::from_cluster_slots(&reply).unwrap();
+        assert_eq!(map.node_for_slot(0), Some(&node(30001)));
+        assert_eq!(map.node_for_key(b"foo"), Some(&node(30002)));
+        assert_eq!(
+            map.route(&["SET", "foo", "bar"]).unwrap(),
+            Some(&node(30002))
+        );
+        assert_eq!(map.route(&["PING"]).unwrap(), None);
+
+        assert!(map.update(&Resp::Error(b"MOVED 12182 :30004")));
+        assert_eq!(map.node_for_key(b"foo"), Some(&node(30004)));
+        assert_eq!(map.node_for_slot(12183), Some(&node(30002)));
+        assert!(!map.update(&Resp::Error(b"ASK 12182 127.0.0.1:30001")));
+        assert!(!map.update(&Resp::String(b"OK")));
+        assert!(!map.update(&Resp::Error(b"MOVED 20000 127.0.0.1:30001")));
+    }
+
+    #[test]
+    pub fn test_cluster_shards() {
+        let input = b"*1\r\n%2\r\n+slots\r\n*4\r\n:0\r\n:99\r\n:200\r\n:299\r\n+nodes\r\n*2\r\n%4\r\n+ip\r\n+10.0.0.2\r\n+port\r\n:7001\r\n+role\r\n+replica\r\n+health\r\n+online\r\n%5\r\n+ip\r\n+10.0.0.1\r\n+endpoint\r\n+redis-0\r\n+port\r\n:7000\r\n+role\r\n+master\r\n+health\r\n+online\r\n";
+        let (reply, _) = parse_resp(input).unwrap();
+        let map = SlotMap::from_cluster_shards(&reply).unwrap();
+        let primary = Node {
+            host: "redis-0".to_string(),
+            port: 7000,
+        };
+        assert_eq!(map.node_for_slot(50), Some(&primary));
+        assert_eq!(map.node_for_slot(250), Some(&primary));
+        assert_eq!(map.node_for_slot(150), None);
+        assert!(map.route(&["GET", "foo"]).is_err());
+    }
+}
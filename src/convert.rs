@@ -0,0 +1,244 @@
+use crate::{Resp, RespError};
+
+/// How a RESP2 reply maps to its RESP3 form, for commands whose reply type changed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Hint {
+    /// The reply has the same shape in both protocols, only nils become nulls.
+    Same,
+    /// A flat array of key/value pairs becomes a map.
+    Map,
+    /// An array of unique elements becomes a set.
+    Set,
+    /// A bulk string holding a number becomes a double.
+    Double,
+    /// Every element of an array is converted with the inner hint.
+    Array(Box<Hint>),
+    /// A flat array of members and scores becomes an array of `[member, score]` pairs.
+    ScoredPairs,
+}
+
+// Commands replying with a nil array rather than a nil bulk string in RESP2
+const NIL_ARRAY_COMMANDS: &[&[u8]] = &[
+    b"BLPOP",
+    b"BRPOP",
+    b"BLMPOP",
+    b"LMPOP",
+    b"BZPOPMIN",
+    b"BZPOPMAX",
+    b"BZMPOP",
+    b"ZMPOP",
+    b"XREAD",
+    b"XREADGROUP",
+    b"EXEC",
+];
+
+fn name<A: AsRef<[u8]>>(command: &[A]) -> Vec<u8> {
+    match command.first() {
+        Some(name) => name.as_ref().to_ascii_uppercase(),
+        None => Vec::new(),
+    }
+}
+
+fn has_arg<A: AsRef<[u8]>>(command: &[A], arg: &[u8]) -> bool {
+    command
+        .iter()
+        .skip(1)
+        .any(|a| a.as_ref().eq_ignore_ascii_case(arg))
+}
+
+/// Hint for the reply to `command`, given as the full list of arguments.
+pub fn hint_for<A: AsRef<[u8]>>(command: &[A]) -> Hint {
+    let subcommand = command
+        .get(1)
+        .map(|s| s.as_ref().to_ascii_uppercase())
+        .unwrap_or_default();
+    match (name(command).as_slice(), subcommand.as_slice()) {
+        (b"HGETALL", _) | (b"CONFIG", b"GET") => Hint::Map,
+        (b"SMEMBERS", _) | (b"SINTER", _) | (b"SUNION", _) | (b"SDIFF", _) => Hint::Set,
+        (b"ZSCORE", _) | (b"ZINCRBY", _) => Hint::Double,
+        (b"ZMSCORE", _) => Hint::Array(Box::new(Hint::Double)),
+        (b"ZRANGE", _)
+        | (b"ZREVRANGE", _)
+        | (b"ZRANGEBYSCORE", _)
+        | (b"ZREVRANGEBYSCORE", _)
+        | (b"ZUNION", _)
+        | (b"ZINTER", _)
+        | (b"ZDIFF", _)
+            if has_arg(command, b"WITHSCORES") =>
+        {
+            Hint::ScoredPairs
+        }
+        _ => Hint::Same,
+    }
+}
+
+/// Converts a RESP3 value to what Redis would have replied to `command` over RESP2.
+///
+/// Maps are flattened into arrays, sets and pushes become arrays, booleans become `0`/`1`,
+/// doubles, big numbers and verbatim strings become bulk strings and attributes are dropped.
+/// Nulls become nil arrays for the commands that reply with one in RESP2, and nil bulk strings
+/// everywhere else.
+///
+/// A streamed string cannot be turned into a bulk string without copying, so only streamed
+/// strings made of a single chunk are accepted.
+pub fn downgrade<'a, A: AsRef<[u8]>>(resp: Resp<'a>, command: &[A]) -> Result<Resp<'a>, RespError> {
+    match resp {
+        Resp::Null if NIL_ARRAY_COMMANDS.contains(&name(command).as_slice()) => Ok(Resp::NilArray),
+        resp => downgrade_value(resp),
+    }
+}
+
+fn downgrade_value(resp: Resp) -> Result<Resp, RespError> {
+    let resp = match resp {
+        Resp::Null => Resp::NilBulk,
+        Resp::Boolean(true) => Resp::Integer(b"1"),
+        Resp::Boolean(false) => Resp::Integer(b"0"),
+        Resp::Double(s) | Resp::BigNumber(s) => Resp::BulkString(s),
+        Resp::BlobError(s) => Resp::Error(s),
+        Resp::Verbatim { text, .. } => Resp::BulkString(text),
+        Resp::Array(a) | Resp::Set(a) | Resp::Push(a) => Resp::Array(
+            a.into_iter()
+                .map(downgrade_value)
+                .collect::<Result<_, _>>()?,
+        ),
+        Resp::Map(m) => {
+            let mut result = Vec::with_capacity(m.len() * 2);
+            for (k, v) in m {
+                result.push(downgrade_value(k)?);
+                result.push(downgrade_value(v)?);
+            }
+            Resp::Array(result)
+        }
+        Resp::StreamedString(c) => match c.as_slice() {
+            [] => Resp::BulkString(b""),
+            [chunk] => Resp::BulkString(chunk),
+            _ => return Err(RespError::IncorrectFormat),
+        },
+        Resp::WithAttributes { value, .. } => downgrade_value(*value)?,
+        resp => resp,
+    };
+    Ok(resp)
+}
+
+/// Converts a RESP2 reply to `command` to what Redis would have replied over RESP3.
+pub fn upgrade<'a, A: AsRef<[u8]>>(resp: Resp<'a>, command: &[A]) -> Result<Resp<'a>, RespError> {
+    upgrade_with(resp, &hint_for(command))
+}
+
+/// Same as [`upgrade`], with an explicit hint for commands [`hint_for`] does not know about.
+///
+/// Fails with [`RespError::IncorrectFormat`] if the reply does not have the shape the hint
+/// expects. Error replies are returned untouched whatever the hint.
+pub fn upgrade_with<'a>(resp: Resp<'a>, hint: &Hint) -> Result<Resp<'a>, RespError> {
+    let resp = match (resp, hint) {
+        (Resp::NilBulk | Resp::NilArray, _) => Resp::Null,
+        (resp @ Resp::Error(_), _) => resp,
+        (Resp::Array(a), Hint::Same) => Resp::Array(
+            a.into_iter()
+                .map(|e| upgrade_with(e, &Hint::Same))
+                .collect::<Result<_, _>>()?,
+        ),
+        (Resp::Array(a), Hint::Map) if a.len() % 2 == 0 => {
+            let mut result = Vec::with_capacity(a.len() / 2);
+            let mut elements = a.into_iter();
+            while let (Some(k), Some(v)) = (elements.next(), elements.next()) {
+                result.push((upgrade_with(k, &Hint::Same)?, upgrade_with(v, &Hint::Same)?));
+            }
+            Resp::Map(result)
+        }
+        (Resp::Array(a), Hint::Set) => Resp::Set(a),
+        (Resp::BulkString(s), Hint::Double) => Resp::Double(s),
+        (Resp::Array(a), Hint::Array(inner)) => Resp::Array(
+            a.into_iter()
+                .map(|e| upgrade_with(e, inner))
+                .collect::<Result<_, _>>()?,
+        ),
+        (Resp::Array(a), Hint::ScoredPairs) if a.len() % 2 == 0 => {
+            let mut result = Vec::with_capacity(a.len() / 2);
+            let mut elements = a.into_iter();
+            while let (Some(member), Some(score)) = (elements.next(), elements.next()) {
+                let score = upgrade_with(score, &Hint::Double)?;
+                result.push(Resp::Array(vec![member, score]));
+            }
+            Resp::Array(result)
+        }
+        (resp, Hint::Same) => resp,
+        _ => return Err(RespError::IncorrectFormat),
+    };
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_resp;
+
+    #[test]
+    pub fn test_downgrade() {
+        let input = b"%2\r\n+a\r\n#t\r\n+b\r\n~2\r\n,1.5\r\n=7\r\ntxt:foo\r\n";
+        let (resp, _) = parse_resp(input).unwrap();
+        assert_eq!(
+            downgrade(resp, &["HELLO"]).unwrap(),
+            Resp::Array(vec![
+                Resp::String(b"a"),
+                Resp::Integer(b"1"),
+                Resp::String(b"b"),
+                Resp::Array(vec![Resp::BulkString(b"1.5"), Resp::BulkString(b"foo")]),
+            ])
+        );
+        assert_eq!(downgrade(Resp::Null, &["GET", "k"]).unwrap(), Resp::NilBulk);
+        assert_eq!(
+            downgrade(Resp::Null, &["blpop", "k", "0"]).unwrap(),
+            Resp::NilArray
+        );
+        let resp = Resp::Array(vec![Resp::Null]);
+        assert_eq!(
+            downgrade(resp, &["EXEC"]).unwrap(),
+            Resp::Array(vec![Resp::NilBulk])
+        );
+        let resp = Resp::WithAttributes {
+            attrs: vec![],
+            value: Box::new(Resp::StreamedString(vec![b"foo"])),
+        };
+        assert_eq!(downgrade(resp, &["GET"]).unwrap(), Resp::BulkString(b"foo"));
+        let resp = Resp::StreamedString(vec![b"foo", b"bar"]);
+        assert!(downgrade(resp, &["GET"]).is_err());
+    }
+
+    #[test]
+    pub fn test_upgrade() {
+        let input = b"*4\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n$-1\r\n";
+        let (resp, _) = parse_resp(input).unwrap();
+        assert_eq!(
+            upgrade(resp, &["HGETALL", "h"]).unwrap(),
+            Resp::Map(vec![
+                (Resp::BulkString(b"a"), Resp::BulkString(b"1")),
+                (Resp::BulkString(b"b"), Resp::Null),
+            ])
+        );
+        let input = b"*4\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n$3\r\n2.5\r\n";
+        let (resp, _) = parse_resp(input).unwrap();
+        assert_eq!(
+            upgrade(resp, &["zrange", "z", "0", "-1", "withscores"]).unwrap(),
+            Resp::Array(vec![
+                Resp::Array(vec![Resp::BulkString(b"a"), Resp::Double(b"1")]),
+                Resp::Array(vec![Resp::BulkString(b"b"), Resp::Double(b"2.5")]),
+            ])
+        );
+        let (resp, _) = parse_resp(b"*2\r\n$3\r\n1.5\r\n$-1\r\n").unwrap();
+        assert_eq!(
+            upgrade(resp, &["ZMSCORE", "z", "a", "b"]).unwrap(),
+            Resp::Array(vec![Resp::Double(b"1.5"), Resp::Null])
+        );
+        assert_eq!(
+            upgrade(Resp::Array(vec![]), &["SMEMBERS", "s"]).unwrap(),
+            Resp::Set(vec![])
+        );
+        assert_eq!(
+            upgrade(Resp::Error(b"WRONGTYPE"), &["HGETALL", "h"]).unwrap(),
+            Resp::Error(b"WRONGTYPE")
+        );
+        let resp = Resp::Array(vec![Resp::BulkString(b"a")]);
+        assert!(upgrade(resp, &["HGETALL", "h"]).is_err());
+    }
+}
@@ -0,0 +1,424 @@
+//! The payloads of `DUMP` and `RESTORE`.
+//!
+//! A payload is a value serialized like in an RDB file, preceded by its type and followed by
+//! the RDB version that wrote it (2 bytes) and a CRC64 of everything before (8 bytes), both
+//! little endian.
+
+use crate::rdb::{
+    backlen_size, crc64, nested, read_value, RdbStream, RdbValue, TYPE_HASH, TYPE_LIST, TYPE_SET,
+    TYPE_STREAM_LISTPACKS, TYPE_STREAM_LISTPACKS_2, TYPE_STREAM_LISTPACKS_3, TYPE_STRING,
+    TYPE_ZSET_2,
+};
+use crate::streams::StreamId;
+use crate::{Resp, RespError};
+
+// Length of the version and checksum
+const FOOTER_LEN: usize = 10;
+
+/// The RDB version written by Redis 5 and 6, which every server since then can restore.
+pub const DEFAULT_VERSION: u16 = 9;
+
+/// A value with the RDB version of its payload.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dump {
+    pub value: RdbValue,
+    /// Servers refuse to restore payloads with a version newer than theirs.
+    pub version: u16,
+}
+
+impl Dump {
+    pub fn new(value: RdbValue) -> Self {
+        Self {
+            value,
+            version: DEFAULT_VERSION,
+        }
+    }
+
+    /// Decodes a payload, after verifying its checksum.
+    pub fn parse(payload: &[u8]) -> Result<Self, RespError> {
+        if payload.len() < FOOTER_LEN + 1 {
+            return Err(RespError::IncorrectFormat);
+        }
+        let (data, checksum) = payload.split_at(payload.len() - 8);
+        let checksum = u64::from_le_bytes(checksum.try_into().unwrap());
+        let crc = crc64(0, data);
+        if checksum != crc {
+            return Err(RespError::Other(
+                format!(
+                    "DUMP payload checksum mismatch: {:x} != {:x}",
+                    checksum, crc
+                )
+                .into(),
+            ));
+        }
+        let (data, version) = data.split_at(data.len() - 2);
+        let version = u16::from_le_bytes(version.try_into().unwrap());
+        let value = nested(data, |c| {
+            let kind = c.u8()?;
+            let value = read_value(c, kind)?;
+            if c.pos != data.len() {
+                return Err(RespError::IncorrectFormat);
+            }
+            Ok(value)
+        })?;
+        Ok(Dump { value, version })
+    }
+
+    /// The reply to `DUMP`, `None` when the key does not exist.
+    pub fn from_reply(reply: &Resp) -> Result<Option<Self>, RespError> {
+        match reply {
+            Resp::NilBulk | Resp::Null => Ok(None),
+            reply => Dump::try_from(reply).map(Some),
+        }
+    }
+
+    /// Encodes the value as a payload to pass to `RESTORE`.
+    ///
+    /// Values use the plainest encoding available, which the server converts on load. Streams
+    /// are written in the format of the version, since each format adds metadata that older
+    /// servers do not know about.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        match &self.value {
+            RdbValue::String(s) => {
+                output.push(TYPE_STRING);
+                write_string(&mut output, s);
+            }
+            RdbValue::List(list) | RdbValue::Set(list) => {
+                let kind = match self.value {
+                    RdbValue::List(_) => TYPE_LIST,
+                    _ => TYPE_SET,
+                };
+                output.push(kind);
+                write_len(&mut output, list.len() as u64);
+                for s in list {
+                    write_string(&mut output, s);
+                }
+            }
+            RdbValue::SortedSet(members) => {
+                output.push(TYPE_ZSET_2);
+                write_len(&mut output, members.len() as u64);
+                for (member, score) in members {
+                    write_string(&mut output, member);
+                    output.extend_from_slice(&score.to_le_bytes());
+                }
+            }
+            RdbValue::Hash(fields) => {
+                output.push(TYPE_HASH);
+                write_len(&mut output, fields.len() as u64);
+                for (field, value) in fields {
+                    write_string(&mut output, field);
+                    write_string(&mut output, value);
+                }
+            }
+            RdbValue::Stream(stream) => write_stream(&mut output, stream, self.version),
+        }
+        output.extend_from_slice(&self.version.to_le_bytes());
+        let crc = crc64(0, &output);
+        output.extend_from_slice(&crc.to_le_bytes());
+        output
+    }
+}
+
+impl<'a, 'b> TryFrom<&'b Resp<'a>> for Dump {
+    type Error = RespError;
+
+    /// Accepts the bulk string returned by `DUMP` for an existing key, see
+    /// [`Dump::from_reply`] for a key that may not exist.
+    fn try_from(from: &'b Resp<'a>) -> Result<Self, Self::Error> {
+        match from {
+            Resp::BulkString(payload) => Dump::parse(payload),
+            _ => Err(RespError::IncorrectFormat),
+        }
+    }
+}
+
+fn write_len(output: &mut Vec<u8>, len: u64) {
+    if len < 1 << 6 {
+        output.push(len as u8);
+    } else if len < 1 << 14 {
+        output.extend_from_slice(&[0x40 | (len >> 8) as u8, len as u8]);
+    } else if len <= u32::MAX as u64 {
+        output.push(0x80);
+        output.extend_from_slice(&(len as u32).to_be_bytes());
+    } else {
+        output.push(0x81);
+        output.extend_from_slice(&len.to_be_bytes());
+    }
+}
+
+fn write_string(output: &mut Vec<u8>, s: &[u8]) {
+    write_len(output, s.len() as u64);
+    output.extend_from_slice(s);
+}
+
+fn write_raw_stream_id(output: &mut Vec<u8>, id: StreamId) {
+    output.extend_from_slice(&id.ms.to_be_bytes());
+    output.extend_from_slice(&id.seq.to_be_bytes());
+}
+
+fn write_stream_id(output: &mut Vec<u8>, id: StreamId) {
+    write_len(output, id.ms);
+    write_len(output, id.seq);
+}
+
+/// Builds a listpack, the encoding of stream entries.
+#[derive(Debug, Default)]
+struct Listpack {
+    entries: Vec<u8>,
+    count: usize,
+}
+
+impl Listpack {
+    fn push_entry(&mut self, entry: &[u8]) {
+        self.entries.extend_from_slice(entry);
+        // The size of the entry, written so that it can be read from its last byte
+        let len = entry.len() as u64;
+        let size = backlen_size(len);
+        for i in (0..size).rev() {
+            let byte = ((len >> (7 * i)) & 127) as u8;
+            self.entries
+                .push(if i == size - 1 { byte } else { byte | 128 });
+        }
+        self.count += 1;
+    }
+
+    fn string(&mut self, s: &[u8]) {
+        let len = s.len();
+        let mut entry = Vec::with_capacity(len + 5);
+        if len < 1 << 6 {
+            entry.push(0x80 | len as u8);
+        } else if len < 1 << 12 {
+            entry.extend_from_slice(&[0xE0 | (len >> 8) as u8, len as u8]);
+        } else {
+            entry.push(0xF0);
+            entry.extend_from_slice(&(len as u32).to_le_bytes());
+        }
+        entry.extend_from_slice(s);
+        self.push_entry(&entry);
+    }
+
+    fn int(&mut self, i: i64) {
+        let entry = match i {
+            0..=127 => vec![i as u8],
+            -4096..=4095 => vec![0xC0 | ((i >> 8) & 0x1f) as u8, i as u8],
+            _ if i16::try_from(i).is_ok() => [&[0xF1][..], &(i as i16).to_le_bytes()].concat(),
+            -8388608..=8388607 => [&[0xF2][..], &(i as i32).to_le_bytes()[..3]].concat(),
+            _ if i32::try_from(i).is_ok() => [&[0xF3][..], &(i as i32).to_le_bytes()].concat(),
+            _ => [&[0xF4][..], &i.to_le_bytes()].concat(),
+        };
+        self.push_entry(&entry);
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let total = self.entries.len() + 7;
+        let mut output = Vec::with_capacity(total);
+        output.extend_from_slice(&(total as u32).to_le_bytes());
+        // The count saturates, readers then walk the whole listpack instead
+        output.extend_from_slice(&(self.count.min(u16::MAX as usize) as u16).to_le_bytes());
+        output.extend_from_slice(&self.entries);
+        output.push(0xFF);
+        output
+    }
+}
+
+// All the entries in a single listpack, relative to the first one, whose fields are used as
+// the master fields.
+fn stream_listpack(stream: &RdbStream) -> Vec<u8> {
+    let (master, master_fields) = &stream.entries[0];
+    let mut lp = Listpack::default();
+    lp.int(stream.entries.len() as i64);
+    lp.int(0);
+    lp.int(master_fields.len() as i64);
+    for (field, _) in master_fields {
+        lp.string(field);
+    }
+    lp.int(0);
+    for (id, fields) in &stream.entries {
+        let same_fields = fields.len() == master_fields.len()
+            && fields.iter().zip(master_fields).all(|(a, b)| a.0 == b.0);
+        lp.int(if same_fields { 2 } else { 0 });
+        lp.int(id.ms.wrapping_sub(master.ms) as i64);
+        lp.int(id.seq.wrapping_sub(master.seq) as i64);
+        if same_fields {
+            for (_, value) in fields {
+                lp.string(value);
+            }
+        } else {
+            lp.int(fields.len() as i64);
+            for (field, value) in fields {
+                lp.string(field);
+                lp.string(value);
+            }
+        }
+        let count = if same_fields {
+            fields.len() + 3
+        } else {
+            fields.len() * 2 + 4
+        };
+        lp.int(count as i64);
+    }
+    lp.finish()
+}
+
+fn write_stream(output: &mut Vec<u8>, stream: &RdbStream, version: u16) {
+    // Redis 7.0 wrote RDB 10 and added type 19, Redis 7.2 wrote RDB 11 and added type 21
+    let kind = match version {
+        0..=9 => TYPE_STREAM_LISTPACKS,
+        10 => TYPE_STREAM_LISTPACKS_2,
+        _ => TYPE_STREAM_LISTPACKS_3,
+    };
+    output.push(kind);
+    if stream.entries.is_empty() {
+        write_len(output, 0);
+    } else {
+        write_len(output, 1);
+        let mut master = Vec::with_capacity(16);
+        write_raw_stream_id(&mut master, stream.entries[0].0);
+        write_string(output, &master);
+        write_string(output, &stream_listpack(stream));
+    }
+    write_len(output, stream.length);
+    write_stream_id(output, stream.last_id);
+    if kind >= TYPE_STREAM_LISTPACKS_2 {
+        let first_id = stream.entries.first().map(|e| e.0).unwrap_or_default();
+        write_stream_id(output, stream.first_id.unwrap_or(first_id));
+        write_stream_id(output, stream.max_deleted_id.unwrap_or_default());
+        write_len(output, stream.entries_added.unwrap_or(stream.length));
+    }
+    write_len(output, stream.groups.len() as u64);
+    for group in &stream.groups {
+        write_string(output, &group.name);
+        write_stream_id(output, group.last_id);
+        if kind >= TYPE_STREAM_LISTPACKS_2 {
+            // -1 tells the server the number of entries read is unknown
+            write_len(output, group.entries_read.unwrap_or(u64::MAX));
+        }
+        write_len(output, group.pending.len() as u64);
+        for pending in &group.pending {
+            write_raw_stream_id(output, pending.id);
+            output.extend_from_slice(&pending.delivery_time.to_le_bytes());
+            write_len(output, pending.delivery_count);
+        }
+        write_len(output, group.consumers.len() as u64);
+        for consumer in &group.consumers {
+            write_string(output, &consumer.name);
+            output.extend_from_slice(&consumer.seen_time.to_le_bytes());
+            if kind >= TYPE_STREAM_LISTPACKS_3 {
+                let active_time = consumer.active_time.unwrap_or(consumer.seen_time);
+                output.extend_from_slice(&active_time.to_le_bytes());
+            }
+            write_len(output, consumer.pending.len() as u64);
+            for id in &consumer.pending {
+                write_raw_stream_id(output, *id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rdb::{RdbConsumer, RdbConsumerGroup, RdbPendingEntry};
+
+    fn roundtrip(dump: Dump) {
+        assert_eq!(Dump::parse(&dump.encode()).unwrap(), dump);
+    }
+
+    #[test]
+    pub fn test_parse() {
+        // DUMP of a key set to 10, from the Redis documentation
+        let reply = Resp::BulkString(b"\x00\xc0\n\t\x00\xbem\x06\x89Z(\x00\n");
+        let dump = Dump::try_from(&reply).unwrap();
+        assert_eq!(dump.value, RdbValue::String(b"10".to_vec()));
+        assert_eq!(dump.version, 9);
+
+        let mut payload = Dump::new(RdbValue::String(b"v".to_vec())).encode();
+        payload[1] ^= 1;
+        assert!(matches!(Dump::parse(&payload), Err(RespError::Other(_))));
+        assert!(Dump::parse(b"\x00").is_err());
+        assert!(Dump::try_from(&Resp::Null).is_err());
+        assert_eq!(Dump::from_reply(&Resp::NilBulk).unwrap(), None);
+        assert_eq!(Dump::from_reply(&reply).unwrap(), Some(dump));
+        assert!(Dump::from_reply(&Resp::Integer(b"1")).is_err());
+    }
+
+    #[test]
+    pub fn test_roundtrip() {
+        let strings = |n: usize| (0..n).map(|i| vec![b'x'; i]).collect::<Vec<_>>();
+        roundtrip(Dump::new(RdbValue::String(vec![b'a'; 20000])));
+        roundtrip(Dump::new(RdbValue::List(strings(100))));
+        roundtrip(Dump::new(RdbValue::Set(strings(3))));
+        roundtrip(Dump::new(RdbValue::SortedSet(vec![
+            (b"a".to_vec(), -1.5),
+            (b"b".to_vec(), f64::INFINITY),
+        ])));
+        roundtrip(Dump::new(RdbValue::Hash(vec![(
+            b"f".to_vec(),
+            b"v".to_vec(),
+        )])));
+    }
+
+    #[test]
+    pub fn test_stream_roundtrip() {
+        let id = |ms, seq| StreamId { ms, seq };
+        let fields = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(f, v)| (f.as_bytes().to_vec(), v.as_bytes().to_vec()))
+                .collect::<Vec<_>>()
+        };
+        let long = "v".repeat(5000);
+        let stream = RdbStream {
+            entries: vec![
+                (id(1000, 0), fields(&[("a", "1"), ("b", "2")])),
+                (id(1000, 1), fields(&[("a", "-300"), ("b", &long)])),
+                (id(9000000000, 7), fields(&[("c", "123456789012")])),
+            ],
+            length: 3,
+            last_id: id(9000000000, 7),
+            first_id: Some(id(1000, 0)),
+            max_deleted_id: Some(id(0, 0)),
+            entries_added: Some(3),
+            groups: vec![RdbConsumerGroup {
+                name: b"group".to_vec(),
+                last_id: id(1000, 1),
+                entries_read: Some(2),
+                pending: vec![RdbPendingEntry {
+                    id: id(1000, 1),
+                    delivery_time: 1700000000000,
+                    delivery_count: 1,
+                }],
+                consumers: vec![RdbConsumer {
+                    name: b"consumer".to_vec(),
+                    seen_time: 1700000000000,
+                    active_time: Some(1700000000001),
+                    pending: vec![id(1000, 1)],
+                }],
+            }],
+        };
+        roundtrip(Dump {
+            value: RdbValue::Stream(stream.clone()),
+            version: 11,
+        });
+
+        // Older formats lose the metadata they do not have
+        let dump = Dump::parse(&Dump::new(RdbValue::Stream(stream.clone())).encode()).unwrap();
+        let RdbValue::Stream(old) = dump.value else {
+            panic!("{:?}", dump.value)
+        };
+        assert_eq!(old.entries, stream.entries);
+        assert_eq!(old.first_id, None);
+        assert_eq!(old.groups[0].consumers[0].active_time, None);
+
+        roundtrip(Dump {
+            value: RdbValue::Stream(RdbStream {
+                first_id: Some(id(0, 0)),
+                max_deleted_id: Some(id(0, 0)),
+                entries_added: Some(0),
+                ..RdbStream::default()
+            }),
+            version: 10,
+        });
+    }
+}
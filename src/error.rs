@@ -0,0 +1,182 @@
+use crate::cluster::SLOT_COUNT;
+use crate::{Resp, RespError};
+
+/// What an error reply is about, from the prefix Redis puts before the message.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RedisErrorKind<'a> {
+    /// The generic `ERR` prefix.
+    Err,
+    WrongType,
+    /// The slot lives on another node, from now on.
+    Moved(Redirection<'a>),
+    /// The slot is being migrated, only the next command goes to the other node.
+    Ask(Redirection<'a>),
+    TryAgain,
+    ClusterDown,
+    CrossSlot,
+    Loading,
+    NoScript,
+    ReadOnly,
+    Busy,
+    BusyKey,
+    MasterDown,
+    NoReplicas,
+    OutOfMemory,
+    NoAuth,
+    WrongPass,
+    NoPerm,
+    NoProto,
+    ExecAbort,
+    /// Any other prefix, or a `MOVED`/`ASK` whose payload could not be parsed.
+    Other(&'a [u8]),
+}
+
+/// Where a cluster redirection points to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Redirection<'a> {
+    /// Always below [`SLOT_COUNT`], redirections to other slots are not parsed as such.
+    pub slot: u16,
+    /// Empty when the node does not know its own address, meaning the host of the node that
+    /// sent the redirection.
+    pub host: &'a str,
+    pub port: u16,
+}
+
+/// An error reply, split into its kind and the whole message.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RedisError<'a> {
+    pub kind: RedisErrorKind<'a>,
+    pub message: &'a [u8],
+}
+
+fn parse_redirection(payload: &[u8]) -> Option<Redirection<'_>> {
+    let payload = std::str::from_utf8(payload).ok()?;
+    let (slot, address) = payload.split_once(' ')?;
+    // IPv6 addresses contain colons too, the port is after the last one
+    let (host, port) = address.rsplit_once(':')?;
+    let slot = slot.parse().ok().filter(|&slot| slot < SLOT_COUNT)?;
+    Some(Redirection {
+        slot,
+        host,
+        port: port.parse().ok()?,
+    })
+}
+
+impl<'a> RedisError<'a> {
+    /// Classifies the message of an error reply, without the leading `-`.
+    pub fn parse(message: &'a [u8]) -> Self {
+        let (code, payload) = match message.iter().position(|&c| c == b' ') {
+            Some(index) => (&message[..index], &message[index + 1..]),
+            None => (message, &b""[..]),
+        };
+        let kind = match code {
+            b"ERR" => RedisErrorKind::Err,
+            b"WRONGTYPE" => RedisErrorKind::WrongType,
+            b"MOVED" => match parse_redirection(payload) {
+                Some(redirection) => RedisErrorKind::Moved(redirection),
+                None => RedisErrorKind::Other(code),
+            },
+            b"ASK" => match parse_redirection(payload) {
+                Some(redirection) => RedisErrorKind::Ask(redirection),
+                None => RedisErrorKind::Other(code),
+            },
+            b"TRYAGAIN" => RedisErrorKind::TryAgain,
+            b"CLUSTERDOWN" => RedisErrorKind::ClusterDown,
+            b"CROSSSLOT" => RedisErrorKind::CrossSlot,
+            b"LOADING" => RedisErrorKind::Loading,
+            b"NOSCRIPT" => RedisErrorKind::NoScript,
+            b"READONLY" => RedisErrorKind::ReadOnly,
+            b"BUSY" => RedisErrorKind::Busy,
+            b"BUSYKEY" => RedisErrorKind::BusyKey,
+            b"MASTERDOWN" => RedisErrorKind::MasterDown,
+            b"NOREPLICAS" => RedisErrorKind::NoReplicas,
+            b"OOM" => RedisErrorKind::OutOfMemory,
+            b"NOAUTH" => RedisErrorKind::NoAuth,
+            b"WRONGPASS" => RedisErrorKind::WrongPass,
+            b"NOPERM" => RedisErrorKind::NoPerm,
+            b"NOPROTO" => RedisErrorKind::NoProto,
+            b"EXECABORT" => RedisErrorKind::ExecAbort,
+            code => RedisErrorKind::Other(code),
+        };
+        RedisError { kind, message }
+    }
+
+    /// Whether sending the same command again later may succeed, once the server is done
+    /// loading, the cluster is back up, a script stopped running...
+    ///
+    /// Redirections are not included, since the command has to go to another node.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind,
+            RedisErrorKind::TryAgain
+                | RedisErrorKind::ClusterDown
+                | RedisErrorKind::Loading
+                | RedisErrorKind::Busy
+                | RedisErrorKind::MasterDown
+        )
+    }
+}
+
+impl<'a, 'b> TryFrom<&'b Resp<'a>> for RedisError<'a> {
+    type Error = RespError;
+
+    /// Fails with [`RespError::IncorrectFormat`] if the value is not an error reply.
+    fn try_from(from: &'b Resp<'a>) -> Result<Self, Self::Error> {
+        match from {
+            Resp::Error(message) | Resp::BlobError(message) => Ok(RedisError::parse(message)),
+            Resp::WithAttributes { value, .. } => RedisError::try_from(value.as_ref()),
+            _ => Err(RespError::IncorrectFormat),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_redirections() {
+        let error = RedisError::parse(b"MOVED 3999 127.0.0.1:6381");
+        assert_eq!(
+            error.kind,
+            RedisErrorKind::Moved(Redirection {
+                slot: 3999,
+                host: "127.0.0.1",
+                port: 6381
+            })
+        );
+        let error = RedisError::try_from(&Resp::Error(b"ASK 12 ::1:7000")).unwrap();
+        assert_eq!(
+            error.kind,
+            RedisErrorKind::Ask(Redirection {
+                slot: 12,
+                host: "::1",
+                port: 7000
+            })
+        );
+        let error = RedisError::parse(b"MOVED 3999 :6381");
+        assert!(matches!(error.kind, RedisErrorKind::Moved(r) if r.host.is_empty()));
+        let error = RedisError::parse(b"MOVED nope");
+        assert_eq!(error.kind, RedisErrorKind::Other(b"MOVED"));
+        let error = RedisError::parse(b"MOVED 20000 127.0.0.1:6381");
+        assert_eq!(error.kind, RedisErrorKind::Other(b"MOVED"));
+        let error = RedisError::parse(b"ASK 16384 127.0.0.1:6381");
+        assert_eq!(error.kind, RedisErrorKind::Other(b"ASK"));
+    }
+
+    #[test]
+    pub fn test_kinds() {
+        let error =
+            RedisError::parse(b"WRONGTYPE Operation against a key holding the wrong kind of value");
+        assert_eq!(error.kind, RedisErrorKind::WrongType);
+        assert!(!error.is_retryable());
+        let error = RedisError::parse(b"LOADING Redis is loading the dataset in memory");
+        assert!(error.is_retryable());
+        assert_eq!(RedisError::parse(b"ERR").kind, RedisErrorKind::Err);
+        assert_eq!(
+            RedisError::parse(b"CUSTOM thing").kind,
+            RedisErrorKind::Other(b"CUSTOM")
+        );
+        assert!(RedisError::try_from(&Resp::String(b"OK")).is_err());
+    }
+}
@@ -0,0 +1,502 @@
+use crate::{parse_everything_until_crlf, parse_everything_until_index, RespError};
+
+/// A single step of a RESP value, as yielded by [`EventParser`].
+///
+/// Aggregates are reported as a start event carrying the number of elements (pairs for
+/// maps), followed by the events of each element and a matching end event. Streamed
+/// aggregates (`*?`, `%?`, `~?`) have their own start events but share the end events, and
+/// streamed strings (`$?`) are reported chunk by chunk.
+///
+/// An attribute map (`|`) is reported like a map, between `AttributeStart` and
+/// `AttributeEnd`, right before the events of the value it is attached to.
+#[derive(Debug, PartialEq)]
+pub enum Event<'a> {
+    SimpleString(&'a [u8]),
+    Error(&'a [u8]),
+    Integer(i64),
+    Bulk(&'a [u8]),
+    NilBulk,
+    NilArray,
+    ArrayStart(usize),
+    ArrayEnd,
+    // RESP3 types
+    Null,
+    Boolean(bool),
+    Double(f64),
+    BigNumber(&'a [u8]),
+    BlobError(&'a [u8]),
+    Verbatim { format: &'a [u8], text: &'a [u8] },
+    MapStart(usize),
+    MapEnd,
+    SetStart(usize),
+    SetEnd,
+    StreamedArrayStart,
+    StreamedMapStart,
+    StreamedSetStart,
+    StreamedStringStart,
+    StringChunk(&'a [u8]),
+    StreamedStringEnd,
+    AttributeStart(usize),
+    AttributeEnd,
+    PushStart(usize),
+    PushEnd,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Aggregate {
+    Array,
+    Map,
+    Set,
+    StreamedString,
+    Attribute,
+    Push,
+}
+
+#[derive(Clone, Debug)]
+struct Frame {
+    kind: Aggregate,
+    // Number of elements left to read, keys and values are counted separately for maps.
+    // Streamed aggregates do not know it and end with `.\r\n` instead.
+    remaining: Option<usize>,
+}
+
+fn end_event<'a>(kind: Aggregate) -> Event<'a> {
+    match kind {
+        Aggregate::Array => Event::ArrayEnd,
+        Aggregate::Map => Event::MapEnd,
+        Aggregate::Set => Event::SetEnd,
+        Aggregate::StreamedString => Event::StreamedStringEnd,
+        Aggregate::Attribute => Event::AttributeEnd,
+        Aggregate::Push => Event::PushEnd,
+    }
+}
+
+/// Pull parser yielding one [`Event`] at a time, without building a [`crate::Resp`] tree.
+///
+/// Like [`crate::chunked::BulkDecoder`], every call consumes what it needs from the front of
+/// the input and returns the leftover. An event is only produced once all of its bytes are
+/// available, so on [`RespError::NotEnoughBytes`] nothing has been consumed and the same call
+/// can be retried with more input.
+#[derive(Debug, Default)]
+pub struct EventParser {
+    stack: Vec<Frame>,
+}
+
+impl EventParser {
+    pub fn new() -> Self {
+        Self { stack: Vec::new() }
+    }
+
+    /// Number of aggregates currently open.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn next_event<'a>(&mut self, input: &'a [u8]) -> Result<(Event<'a>, &'a [u8]), RespError> {
+        if let Some(frame) = self.stack.last() {
+            match (frame.kind, frame.remaining, input) {
+                (_, Some(0), _) => {
+                    let event = end_event(frame.kind);
+                    self.stack.pop();
+                    return Ok((event, input));
+                }
+                (Aggregate::StreamedString, _, _) => {
+                    let (chunk, leftover) = parse_chunk(input)?;
+                    if chunk.is_empty() {
+                        self.stack.pop();
+                        return Ok((Event::StreamedStringEnd, leftover));
+                    }
+                    return Ok((Event::StringChunk(chunk), leftover));
+                }
+                (kind, None, [b'.', ..]) => {
+                    let leftover = match parse_everything_until_crlf(&input[1..])? {
+                        (b"", leftover) => leftover,
+                        _ => return Err(RespError::IncorrectFormat),
+                    };
+                    self.stack.pop();
+                    return Ok((end_event(kind), leftover));
+                }
+                _ => {}
+            }
+        }
+
+        let (event, leftover, opened) = parse_event(input)?;
+        // Attributes belong to the next value and do not count as an element of their own
+        let is_element = !matches!(event, Event::AttributeStart(_));
+        if let Some(Frame {
+            remaining: Some(remaining),
+            ..
+        }) = self.stack.last_mut()
+        {
+            if is_element {
+                *remaining -= 1;
+            }
+        }
+        if let Some(frame) = opened {
+            self.stack.push(frame);
+        }
+        Ok((event, leftover))
+    }
+
+    /// Consumes the innermost open aggregate up to and including its end event.
+    ///
+    /// This is meant to be called right after a start event to skip a subtree the caller is
+    /// not interested in. If the subtree is not complete yet, the parser is left untouched.
+    pub fn skip<'a>(&mut self, input: &'a [u8]) -> Result<&'a [u8], RespError> {
+        let target = match self.stack.len() {
+            0 => return Ok(input),
+            depth => depth - 1,
+        };
+        let saved = self.stack.clone();
+        let mut left = input;
+        while self.stack.len() > target {
+            match self.next_event(left) {
+                Ok((_, tmp)) => left = tmp,
+                Err(err) => {
+                    self.stack = saved;
+                    return Err(err);
+                }
+            }
+        }
+        Ok(left)
+    }
+}
+
+fn parse_len(input: &[u8]) -> Result<(i64, &[u8]), RespError> {
+    let (size_str, leftover) = parse_everything_until_crlf(input)?;
+    let size = std::str::from_utf8(size_str)?.parse::<i64>()?;
+    Ok((size, leftover))
+}
+
+fn parse_blob(input: &[u8]) -> Result<(Option<&[u8]>, &[u8]), RespError> {
+    let (size, leftover) = parse_len(input)?;
+    if size < 0 {
+        Ok((None, leftover))
+    } else {
+        let (blob, leftover) = parse_everything_until_index(leftover, size as usize)?;
+        Ok((Some(blob), leftover))
+    }
+}
+
+fn parse_chunk(input: &[u8]) -> Result<(&[u8], &[u8]), RespError> {
+    match input {
+        [] => Err(RespError::NotEnoughBytes),
+        // The terminating chunk has a zero length and no payload at all
+        [b';', body @ ..] => match parse_len(body)? {
+            (0, leftover) => Ok((&[], leftover)),
+            (size, leftover) if size > 0 => parse_everything_until_index(leftover, size as usize),
+            _ => Err(RespError::IncorrectFormat),
+        },
+        _ => Err(RespError::IncorrectFormat),
+    }
+}
+
+fn open(kind: Aggregate, size: usize) -> Result<Option<Frame>, RespError> {
+    let remaining = match kind {
+        Aggregate::Map | Aggregate::Attribute => size.checked_mul(2),
+        _ => Some(size),
+    };
+    Ok(Some(Frame {
+        kind,
+        remaining: Some(remaining.ok_or(RespError::IncorrectFormat)?),
+    }))
+}
+
+fn open_streamed(kind: Aggregate) -> Option<Frame> {
+    Some(Frame {
+        kind,
+        remaining: None,
+    })
+}
+
+type EventResult<'a> = Result<(Event<'a>, &'a [u8], Option<Frame>), RespError>;
+
+fn parse_event(input: &[u8]) -> EventResult<'_> {
+    if input.is_empty() {
+        return Err(RespError::NotEnoughBytes);
+    }
+    let body = &input[1..];
+    if let Some(leftover) = body.strip_prefix(b"?\r\n") {
+        let (event, kind) = match input[0] {
+            b'$' => (Event::StreamedStringStart, Aggregate::StreamedString),
+            b'*' => (Event::StreamedArrayStart, Aggregate::Array),
+            b'%' => (Event::StreamedMapStart, Aggregate::Map),
+            b'~' => (Event::StreamedSetStart, Aggregate::Set),
+            _ => return Err(RespError::IncorrectFormat),
+        };
+        return Ok((event, leftover, open_streamed(kind)));
+    }
+    match input[0] {
+        b'+' => parse_everything_until_crlf(body).map(|(x, y)| (Event::SimpleString(x), y, None)),
+        b'-' => parse_everything_until_crlf(body).map(|(x, y)| (Event::Error(x), y, None)),
+        b':' => {
+            let (size, leftover) = parse_len(body)?;
+            Ok((Event::Integer(size), leftover, None))
+        }
+        b'$' => match parse_blob(body)? {
+            (Some(blob), leftover) => Ok((Event::Bulk(blob), leftover, None)),
+            (None, leftover) => Ok((Event::NilBulk, leftover, None)),
+        },
+        b'*' => match parse_len(body)? {
+            (size, leftover) if size < 0 => Ok((Event::NilArray, leftover, None)),
+            (size, leftover) => {
+                let size = size as usize;
+                Ok((
+                    Event::ArrayStart(size),
+                    leftover,
+                    open(Aggregate::Array, size)?,
+                ))
+            }
+        },
+        b'%' => match parse_len(body)? {
+            (size, leftover) if size >= 0 => {
+                let size = size as usize;
+                Ok((Event::MapStart(size), leftover, open(Aggregate::Map, size)?))
+            }
+            _ => Err(RespError::IncorrectFormat),
+        },
+        b'~' => match parse_len(body)? {
+            (size, leftover) if size >= 0 => {
+                let size = size as usize;
+                Ok((Event::SetStart(size), leftover, open(Aggregate::Set, size)?))
+            }
+            _ => Err(RespError::IncorrectFormat),
+        },
+        b'|' => match parse_len(body)? {
+            (size, leftover) if size >= 0 => {
+                let size = size as usize;
+                Ok((
+                    Event::AttributeStart(size),
+                    leftover,
+                    open(Aggregate::Attribute, size)?,
+                ))
+            }
+            _ => Err(RespError::IncorrectFormat),
+        },
+        b'>' => match parse_len(body)? {
+            (size, leftover) if size >= 0 => {
+                let size = size as usize;
+                Ok((
+                    Event::PushStart(size),
+                    leftover,
+                    open(Aggregate::Push, size)?,
+                ))
+            }
+            _ => Err(RespError::IncorrectFormat),
+        },
+        b'_' => match parse_everything_until_crlf(body)? {
+            (b"", leftover) => Ok((Event::Null, leftover, None)),
+            _ => Err(RespError::IncorrectFormat),
+        },
+        b'#' => match parse_everything_until_crlf(body)? {
+            (b"t", leftover) => Ok((Event::Boolean(true), leftover, None)),
+            (b"f", leftover) => Ok((Event::Boolean(false), leftover, None)),
+            _ => Err(RespError::IncorrectFormat),
+        },
+        b',' => {
+            let (double, leftover) = parse_everything_until_crlf(body)?;
+            let double = std::str::from_utf8(double)?.parse::<f64>()?;
+            Ok((Event::Double(double), leftover, None))
+        }
+        b'(' => parse_everything_until_crlf(body).map(|(x, y)| (Event::BigNumber(x), y, None)),
+        b'!' => match parse_blob(body)? {
+            (Some(blob), leftover) => Ok((Event::BlobError(blob), leftover, None)),
+            (None, _) => Err(RespError::IncorrectFormat),
+        },
+        b'=' => match parse_blob(body)? {
+            (Some(blob), leftover) if blob.len() >= 4 && blob[3] == b':' => {
+                let event = Event::Verbatim {
+                    format: &blob[..3],
+                    text: &blob[4..],
+                };
+                Ok((event, leftover, None))
+            }
+            _ => Err(RespError::IncorrectFormat),
+        },
+        _ => parse_everything_until_crlf(input).map(|(x, y)| (Event::SimpleString(x), y, None)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn collect(input: &[u8]) -> Vec<Event<'_>> {
+        let mut parser = EventParser::new();
+        let mut left = input;
+        let mut events = Vec::new();
+        loop {
+            let (event, tmp) = parser.next_event(left).unwrap();
+            events.push(event);
+            left = tmp;
+            if parser.depth() == 0 {
+                break;
+            }
+        }
+        assert!(left.is_empty());
+        events
+    }
+
+    #[test]
+    pub fn test_resp2_events() {
+        let events = collect(b"*3\r\n:-42\r\n*0\r\n*2\r\n$3\r\nfoo\r\n$-1\r\n");
+        assert_eq!(
+            events,
+            vec![
+                Event::ArrayStart(3),
+                Event::Integer(-42),
+                Event::ArrayStart(0),
+                Event::ArrayEnd,
+                Event::ArrayStart(2),
+                Event::Bulk(b"foo"),
+                Event::NilBulk,
+                Event::ArrayEnd,
+                Event::ArrayEnd,
+            ]
+        );
+        assert_eq!(collect(b"*-1\r\n"), vec![Event::NilArray]);
+        assert_eq!(collect(b"-ERR bad\r\n"), vec![Event::Error(b"ERR bad")]);
+    }
+
+    #[test]
+    pub fn test_resp3_events() {
+        let events = collect(
+            b"%2\r\n+first\r\n#t\r\n$6\r\nsecond\r\n~3\r\n_\r\n,3.5\r\n(12345678901234567890\r\n",
+        );
+        assert_eq!(
+            events,
+            vec![
+                Event::MapStart(2),
+                Event::SimpleString(b"first"),
+                Event::Boolean(true),
+                Event::Bulk(b"second"),
+                Event::SetStart(3),
+                Event::Null,
+                Event::Double(3.5),
+                Event::BigNumber(b"12345678901234567890"),
+                Event::SetEnd,
+                Event::MapEnd,
+            ]
+        );
+        assert_eq!(
+            collect(b"=15\r\ntxt:Some string\r\n"),
+            vec![Event::Verbatim {
+                format: b"txt",
+                text: b"Some string"
+            }]
+        );
+        assert_eq!(
+            collect(b">2\r\n+invalidate\r\n*-1\r\n"),
+            vec![
+                Event::PushStart(2),
+                Event::SimpleString(b"invalidate"),
+                Event::NilArray,
+                Event::PushEnd
+            ]
+        );
+        assert_eq!(
+            collect(b"!10\r\nERR failed\r\n"),
+            vec![Event::BlobError(b"ERR failed")]
+        );
+    }
+
+    #[test]
+    pub fn test_streamed_events() {
+        let events = collect(
+            b"*?\r\n$?\r\n;4\r\nHell\r\n;1\r\no\r\n;0\r\n%?\r\n+a\r\n:1\r\n.\r\n~0\r\n.\r\n",
+        );
+        assert_eq!(
+            events,
+            vec![
+                Event::StreamedArrayStart,
+                Event::StreamedStringStart,
+                Event::StringChunk(b"Hell"),
+                Event::StringChunk(b"o"),
+                Event::StreamedStringEnd,
+                Event::StreamedMapStart,
+                Event::SimpleString(b"a"),
+                Event::Integer(1),
+                Event::MapEnd,
+                Event::SetStart(0),
+                Event::SetEnd,
+                Event::ArrayEnd,
+            ]
+        );
+        let mut parser = EventParser::new();
+        let (_, left) = parser.next_event(b"$?\r\n+OK\r\n").unwrap();
+        let err = parser.next_event(left).unwrap_err();
+        assert!(matches!(err, RespError::IncorrectFormat));
+    }
+
+    #[test]
+    pub fn test_attribute_events() {
+        let events = collect(b"*2\r\n|1\r\n+ttl\r\n:3600\r\n$1\r\nv\r\n:1\r\n");
+        assert_eq!(
+            events,
+            vec![
+                Event::ArrayStart(2),
+                Event::AttributeStart(1),
+                Event::SimpleString(b"ttl"),
+                Event::Integer(3600),
+                Event::AttributeEnd,
+                Event::Bulk(b"v"),
+                Event::Integer(1),
+                Event::ArrayEnd,
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_incomplete_input_is_retryable() {
+        let mut parser = EventParser::new();
+        let (event, left) = parser.next_event(b"*2\r\n$3\r\nfo").unwrap();
+        assert_eq!(event, Event::ArrayStart(2));
+        let err = parser.next_event(left).unwrap_err();
+        assert!(matches!(err, RespError::NotEnoughBytes));
+        let (event, left) = parser.next_event(b"$3\r\nfoo\r\n:1\r\n").unwrap();
+        assert_eq!(event, Event::Bulk(b"foo"));
+        let (event, left) = parser.next_event(left).unwrap();
+        assert_eq!(event, Event::Integer(1));
+        let (event, _) = parser.next_event(left).unwrap();
+        assert_eq!(event, Event::ArrayEnd);
+        assert_eq!(parser.depth(), 0);
+    }
+
+    #[test]
+    pub fn test_skip_subtree() {
+        let mut parser = EventParser::new();
+        let input = b"*3\r\n*2\r\n:1\r\n*1\r\n:2\r\n$3\r\nfoo\r\n:3\r\n";
+        let (_, left) = parser.next_event(input).unwrap();
+        let (event, left) = parser.next_event(left).unwrap();
+        assert_eq!(event, Event::ArrayStart(2));
+        let err = parser.skip(&left[..6]).unwrap_err();
+        assert!(matches!(err, RespError::NotEnoughBytes));
+        assert_eq!(parser.depth(), 2);
+        let left = parser.skip(left).unwrap();
+        assert_eq!(parser.depth(), 1);
+        let (event, left) = parser.next_event(left).unwrap();
+        assert_eq!(event, Event::Bulk(b"foo"));
+        let (event, left) = parser.next_event(left).unwrap();
+        assert_eq!(event, Event::Integer(3));
+        let (event, left) = parser.next_event(left).unwrap();
+        assert_eq!(event, Event::ArrayEnd);
+        assert!(left.is_empty());
+    }
+
+    #[test]
+    pub fn test_invalid_events() {
+        let mut parser = EventParser::new();
+        let err = parser.next_event(b":abc\r\n").unwrap_err();
+        assert!(matches!(err, RespError::Other(_)));
+        let err = parser.next_event(b"#x\r\n").unwrap_err();
+        assert!(matches!(err, RespError::IncorrectFormat));
+        let err = parser.next_event(b"%-1\r\n").unwrap_err();
+        assert!(matches!(err, RespError::IncorrectFormat));
+        let err = parser.next_event(b"$3\r\nfooo").unwrap_err();
+        assert!(matches!(err, RespError::IncorrectFormat));
+        let (event, _) = parser.next_event(b"|9223372036854775807\r\n").unwrap();
+        assert_eq!(event, Event::AttributeStart(9223372036854775807));
+        let err = parser.next_event(b"").unwrap_err();
+        assert!(matches!(err, RespError::NotEnoughBytes));
+    }
+}
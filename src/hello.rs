@@ -0,0 +1,198 @@
+use crate::client::encode_command;
+use crate::error::{RedisError, RedisErrorKind};
+use crate::{Resp, RespError};
+use std::io::Write;
+
+/// A `HELLO` command, switching protocols and optionally authenticating and naming the
+/// connection in the same round trip.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Hello {
+    pub protover: u8,
+    pub auth: Option<(Vec<u8>, Vec<u8>)>,
+    pub setname: Option<Vec<u8>>,
+}
+
+impl Hello {
+    pub fn new(protover: u8) -> Self {
+        Self {
+            protover,
+            auth: None,
+            setname: None,
+        }
+    }
+
+    pub fn auth(mut self, username: &[u8], password: &[u8]) -> Self {
+        self.auth = Some((username.to_vec(), password.to_vec()));
+        self
+    }
+
+    pub fn setname(mut self, name: &[u8]) -> Self {
+        self.setname = Some(name.to_vec());
+        self
+    }
+
+    /// The same command asking for another protocol version.
+    pub fn with_protover(&self, protover: u8) -> Self {
+        Self {
+            protover,
+            ..self.clone()
+        }
+    }
+
+    pub fn write_to_writer<W: Write>(&self, writer: &mut W) -> Result<(), RespError> {
+        let protover = self.protover.to_string();
+        let mut args: Vec<&[u8]> = vec![b"HELLO", protover.as_bytes()];
+        if let Some((username, password)) = &self.auth {
+            args.extend_from_slice(&[b"AUTH", username, password]);
+        }
+        if let Some(name) = &self.setname {
+            args.extend_from_slice(&[b"SETNAME", name]);
+        }
+        encode_command(&args, writer)
+    }
+}
+
+/// The server properties returned by `HELLO`.
+///
+/// Only `server`, `version` and `proto` are required, since servers speaking the protocol
+/// without being Redis do not always send the other fields. Unknown fields are ignored.
+#[derive(Debug, Eq, PartialEq)]
+pub struct HelloResponse<'a> {
+    pub server: &'a [u8],
+    pub version: &'a [u8],
+    pub proto: i64,
+    pub id: Option<i64>,
+    pub mode: Option<&'a [u8]>,
+    pub role: Option<&'a [u8]>,
+    pub modules: Vec<Resp<'a>>,
+}
+
+/// Whether the reply is the error sent by servers that do not support the requested protocol
+/// version, in which case the client can retry with RESP2.
+pub fn is_noproto(reply: &Resp) -> bool {
+    matches!(
+        RedisError::try_from(reply),
+        Ok(RedisError {
+            kind: RedisErrorKind::NoProto,
+            ..
+        })
+    )
+}
+
+/// Whether the reply is the error sent by servers older than 6.0, which have no `HELLO` and
+/// only speak RESP2.
+pub fn is_unknown_command(reply: &Resp) -> bool {
+    match RedisError::try_from(reply) {
+        Ok(RedisError {
+            kind: RedisErrorKind::Err,
+            message,
+        }) => message
+            .get(..b"ERR unknown command".len())
+            .is_some_and(|m| m.eq_ignore_ascii_case(b"ERR unknown command")),
+        _ => false,
+    }
+}
+
+impl<'a, 'b> TryFrom<&'b Resp<'a>> for HelloResponse<'a> {
+    type Error = RespError;
+
+    /// Accepts both the RESP3 map and the flat array of keys and values sent over RESP2.
+    fn try_from(from: &'b Resp<'a>) -> Result<Self, Self::Error> {
+        let pairs: Vec<(&Resp<'a>, &Resp<'a>)> = match from {
+            Resp::Map(m) => m.iter().map(|(k, v)| (k, v)).collect(),
+            Resp::Array(a) if a.len() % 2 == 0 => {
+                a.chunks(2).map(|pair| (&pair[0], &pair[1])).collect()
+            }
+            Resp::WithAttributes { value, .. } => return HelloResponse::try_from(value.as_ref()),
+            _ => return Err(RespError::IncorrectFormat),
+        };
+        let (mut server, mut version, mut proto) = (None, None, None);
+        let (mut id, mut mode, mut role, mut modules) = (None, None, None, Vec::new());
+        for (key, value) in pairs {
+            let key = key.as_bytes().ok_or(RespError::IncorrectFormat)?;
+            let bytes = || value.as_bytes().ok_or(RespError::IncorrectFormat);
+            let integer = || value.as_integer().ok_or(RespError::IncorrectFormat);
+            match key {
+                b"server" => server = Some(bytes()?),
+                b"version" => version = Some(bytes()?),
+                b"proto" => proto = Some(integer()?),
+                b"id" => id = Some(integer()?),
+                b"mode" => mode = Some(bytes()?),
+                b"role" => role = Some(bytes()?),
+                b"modules" => match value {
+                    Resp::Array(a) | Resp::Set(a) => modules = a.clone(),
+                    _ => return Err(RespError::IncorrectFormat),
+                },
+                _ => {}
+            }
+        }
+        match (server, version, proto) {
+            (Some(server), Some(version), Some(proto)) => Ok(HelloResponse {
+                server,
+                version,
+                proto,
+                id,
+                mode,
+                role,
+                modules,
+            }),
+            _ => Err(RespError::IncorrectFormat),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_resp;
+
+    #[test]
+    pub fn test_encode() {
+        let mut output = Vec::new();
+        let hello = Hello::new(3).auth(b"user", b"pass").setname(b"app");
+        hello.write_to_writer(&mut output).unwrap();
+        assert_eq!(
+            output,
+            b"*7\r\n$5\r\nHELLO\r\n$1\r\n3\r\n$4\r\nAUTH\r\n$4\r\nuser\r\n$4\r\npass\r\n$7\r\nSETNAME\r\n$3\r\napp\r\n".to_vec()
+        );
+    }
+
+    #[test]
+    pub fn test_hello_response() {
+        let input = b"%7\r\n$6\r\nserver\r\n$5\r\nredis\r\n$7\r\nversion\r\n$5\r\n7.2.4\r\n$5\r\nproto\r\n:3\r\n$2\r\nid\r\n:5\r\n$4\r\nmode\r\n$10\r\nstandalone\r\n$4\r\nrole\r\n$6\r\nmaster\r\n$7\r\nmodules\r\n*0\r\n";
+        let (resp, _) = parse_resp(input).unwrap();
+        let expected = HelloResponse {
+            server: b"redis",
+            version: b"7.2.4",
+            proto: 3,
+            id: Some(5),
+            mode: Some(b"standalone"),
+            role: Some(b"master"),
+            modules: vec![],
+        };
+        assert_eq!(HelloResponse::try_from(&resp).unwrap(), expected);
+        let input = b"*14\r\n$6\r\nserver\r\n$5\r\nredis\r\n$7\r\nversion\r\n$5\r\n7.2.4\r\n$5\r\nproto\r\n:3\r\n$2\r\nid\r\n:5\r\n$4\r\nmode\r\n$10\r\nstandalone\r\n$4\r\nrole\r\n$6\r\nmaster\r\n$7\r\nmodules\r\n*0\r\n";
+        let (resp, _) = parse_resp(input).unwrap();
+        assert_eq!(HelloResponse::try_from(&resp).unwrap(), expected);
+        let (resp, _) = parse_resp(b"*2\r\n$6\r\nserver\r\n$5\r\nredis\r\n").unwrap();
+        assert!(HelloResponse::try_from(&resp).is_err());
+    }
+
+    #[test]
+    pub fn test_noproto() {
+        assert!(is_noproto(&Resp::Error(
+            b"NOPROTO sorry, this protocol version is not supported"
+        )));
+        assert!(!is_noproto(&Resp::Error(b"ERR unknown command 'HELLO'")));
+        assert!(is_unknown_command(&Resp::Error(
+            b"ERR unknown command 'HELLO'"
+        )));
+        assert!(is_unknown_command(&Resp::Error(
+            b"ERR unknown command `HELLO`, with args beginning with: `3`, "
+        )));
+        assert!(!is_unknown_command(&Resp::Error(
+            b"NOPROTO sorry, this protocol version is not supported"
+        )));
+        assert!(!is_unknown_command(&Resp::Error(b"ERR syntax error")));
+    }
+}
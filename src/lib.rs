@@ -1,11 +1,39 @@
 use std::io::Write;
 
+pub mod aof;
+#[cfg(any(feature = "futures-io", feature = "tokio"))]
+pub mod async_io;
+pub mod chunked;
+pub mod client;
+pub mod cluster;
+pub mod convert;
+pub mod dump;
+pub mod error;
+pub mod event;
+pub mod hello;
+pub mod mock;
+mod owned;
+pub mod pubsub;
+pub mod push;
+pub mod rdb;
+pub mod reader;
+pub mod replication;
+pub mod reply;
+pub mod sentinel;
+pub mod server;
+pub mod streams;
+pub mod tracking;
+pub mod transaction;
+pub mod writer;
+
+pub use owned::OwnedResp;
+
 type RespResult<'a> = std::result::Result<(Resp<'a>, &'a [u8]), RespError>;
 
 const CR: u8 = b'\r';
 const LF: u8 = b'\n';
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Resp<'a> {
     String(&'a [u8]),
     Error(&'a [u8]),
@@ -14,6 +42,27 @@ pub enum Resp<'a> {
     NilBulk,
     Array(Vec<Resp<'a>>),
     NilArray,
+    // RESP3 types
+    Null,
+    Boolean(bool),
+    Double(&'a [u8]),
+    BigNumber(&'a [u8]),
+    BlobError(&'a [u8]),
+    Verbatim {
+        format: &'a [u8],
+        text: &'a [u8],
+    },
+    Map(Vec<(Resp<'a>, Resp<'a>)>),
+    Set(Vec<Resp<'a>>),
+    // Chunks of a `$?` streamed string, in order and without the terminating empty chunk
+    StreamedString(Vec<&'a [u8]>),
+    // A value preceded by a `|` attribute map
+    WithAttributes {
+        attrs: Vec<(Resp<'a>, Resp<'a>)>,
+        value: Box<Resp<'a>>,
+    },
+    // Out-of-band data sent by the server, such as pubsub messages
+    Push(Vec<Resp<'a>>),
 }
 
 impl<'a> Resp<'a> {
@@ -26,6 +75,19 @@ impl<'a> Resp<'a> {
             Resp::NilBulk => 5, // $-1\r\n
             Resp::Array(a) => a.iter().map(|s| s.len()).sum(),
             Resp::NilArray => 5, // *-1\r\n
+            Resp::Null => 3,     // _\r\n
+            Resp::Boolean(_) => 4,
+            Resp::Double(s) => s.len() + 1,
+            Resp::BigNumber(s) => s.len() + 1,
+            Resp::BlobError(s) => s.len() + 1,
+            Resp::Verbatim { format, text } => format.len() + text.len() + 2,
+            Resp::Map(m) => m.iter().map(|(k, v)| k.len() + v.len()).sum(),
+            Resp::Set(a) => a.iter().map(|s| s.len()).sum(),
+            Resp::StreamedString(c) => c.iter().map(|s| s.len()).sum::<usize>() + 1,
+            Resp::WithAttributes { attrs, value } => {
+                attrs.iter().map(|(k, v)| k.len() + v.len()).sum::<usize>() + value.len()
+            }
+            Resp::Push(a) => a.iter().map(|s| s.len()).sum(),
         }
     }
 
@@ -64,12 +126,103 @@ impl<'a> Resp<'a> {
                 for s in a {
                     s.write_to_writer(writer)?
                 }
-                writer.write_all(&[b'\r', b'\n'])?;
             }
             Resp::NilArray => writer.write_all(&[b'*', b'-', b'1', b'\r', b'\n'])?,
+            Resp::Null => writer.write_all(b"_\r\n")?,
+            Resp::Boolean(true) => writer.write_all(b"#t\r\n")?,
+            Resp::Boolean(false) => writer.write_all(b"#f\r\n")?,
+            Resp::Double(s) => {
+                writer.write_all(b",")?;
+                writer.write_all(s)?;
+                writer.write_all(b"\r\n")?;
+            }
+            Resp::BigNumber(s) => {
+                writer.write_all(b"(")?;
+                writer.write_all(s)?;
+                writer.write_all(b"\r\n")?;
+            }
+            Resp::BlobError(s) => {
+                writer.write_all(format!("!{}\r\n", s.len()).as_bytes())?;
+                writer.write_all(s)?;
+                writer.write_all(b"\r\n")?;
+            }
+            Resp::Verbatim { format, text } => {
+                let len = format.len() + 1 + text.len();
+                writer.write_all(format!("={}\r\n", len).as_bytes())?;
+                writer.write_all(format)?;
+                writer.write_all(b":")?;
+                writer.write_all(text)?;
+                writer.write_all(b"\r\n")?;
+            }
+            Resp::Map(m) => {
+                writer.write_all(format!("%{}\r\n", m.len()).as_bytes())?;
+                for (k, v) in m {
+                    k.write_to_writer(writer)?;
+                    v.write_to_writer(writer)?;
+                }
+            }
+            Resp::Set(a) => {
+                writer.write_all(format!("~{}\r\n", a.len()).as_bytes())?;
+                for s in a {
+                    s.write_to_writer(writer)?
+                }
+            }
+            Resp::StreamedString(c) => {
+                let mut chunks = writer::StreamedStringWriter::new(&mut *writer)?;
+                for s in c {
+                    chunks.write_chunk(s)?;
+                }
+                chunks.finish()?;
+            }
+            Resp::WithAttributes { attrs, value } => {
+                writer.write_all(format!("|{}\r\n", attrs.len()).as_bytes())?;
+                for (k, v) in attrs {
+                    k.write_to_writer(writer)?;
+                    v.write_to_writer(writer)?;
+                }
+                value.write_to_writer(writer)?;
+            }
+            Resp::Push(a) => {
+                writer.write_all(format!(">{}\r\n", a.len()).as_bytes())?;
+                for s in a {
+                    s.write_to_writer(writer)?
+                }
+            }
         };
         Ok(())
     }
+
+    /// Content of a simple, bulk or verbatim string.
+    pub fn as_bytes(&self) -> Option<&'a [u8]> {
+        match self {
+            Resp::String(s) | Resp::BulkString(s) => Some(s),
+            Resp::Verbatim { text, .. } => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Resp::Integer(s) => std::str::from_utf8(s).ok()?.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Recursively drops every attribute map, keeping only the values they were attached to.
+    pub fn without_attributes(self) -> Resp<'a> {
+        match self {
+            Resp::WithAttributes { value, .. } => value.without_attributes(),
+            Resp::Array(a) => Resp::Array(a.into_iter().map(Resp::without_attributes).collect()),
+            Resp::Set(a) => Resp::Set(a.into_iter().map(Resp::without_attributes).collect()),
+            Resp::Push(a) => Resp::Push(a.into_iter().map(Resp::without_attributes).collect()),
+            Resp::Map(m) => Resp::Map(
+                m.into_iter()
+                    .map(|(k, v)| (k.without_attributes(), v.without_attributes()))
+                    .collect(),
+            ),
+            resp => resp,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -105,6 +258,12 @@ impl From<std::num::ParseIntError> for RespError {
     }
 }
 
+impl From<std::num::ParseFloatError> for RespError {
+    fn from(from: std::num::ParseFloatError) -> Self {
+        Self::Other(Box::new(from))
+    }
+}
+
 impl From<std::io::Error> for RespError {
     fn from(from: std::io::Error) -> Self {
         Self::Other(Box::new(from))
@@ -121,13 +280,30 @@ pub fn parse_resp(input: &[u8]) -> RespResult {
             b'$' => parse_bulk_strings(&input[1..])?,
             b'*' => parse_arrays(&input[1..])?,
             b'-' => parse_errors(&input[1..])?,
+            b'%' => parse_maps(&input[1..])?,
+            b'~' => parse_sets(&input[1..])?,
+            b'_' => parse_null(&input[1..])?,
+            b'#' => parse_booleans(&input[1..])?,
+            b',' => parse_doubles(&input[1..])?,
+            b'(' => parse_big_numbers(&input[1..])?,
+            b'!' => parse_blob_errors(&input[1..])?,
+            b'=' => parse_verbatim_strings(&input[1..])?,
+            b'|' => parse_attributes(&input[1..])?,
+            b'>' => parse_pushes(&input[1..])?,
             _ => parse_simple_string(input)?,
         };
         return Ok((resp, leftover));
     }
 }
 
-fn parse_everything_until_crlf(input: &[u8]) -> std::result::Result<(&[u8], &[u8]), RespError> {
+/// Same as [`parse_resp`], for clients that do not care about RESP3 attributes.
+pub fn parse_resp_without_attributes(input: &[u8]) -> RespResult<'_> {
+    parse_resp(input).map(|(resp, leftover)| (resp.without_attributes(), leftover))
+}
+
+pub(crate) fn parse_everything_until_crlf(
+    input: &[u8],
+) -> std::result::Result<(&[u8], &[u8]), RespError> {
     for (index, (first, second)) in input.iter().zip(input.iter().skip(1)).enumerate() {
         if first == &CR && second == &LF {
             return Ok((&input[0..index], &input[index + 2..]));
@@ -136,10 +312,17 @@ fn parse_everything_until_crlf(input: &[u8]) -> std::result::Result<(&[u8], &[u8
     Err(RespError::NotEnoughBytes)
 }
 
-fn parse_everything_until_index(input: &[u8], index: usize) -> Result<(&[u8], &[u8]), RespError> {
-    if input.len() < index {
+pub(crate) fn parse_everything_until_index(
+    input: &[u8],
+    index: usize,
+) -> Result<(&[u8], &[u8]), RespError> {
+    if input.len() < index + 2 {
+        // The trailing CRLF is not fully there yet, but whatever is there must still match.
+        if input.len() == index + 1 && input[index] != CR {
+            return Err(RespError::IncorrectFormat);
+        }
         return Err(RespError::NotEnoughBytes);
-    } else if input[index] == b'\r' && input[index + 1] == b'\n' {
+    } else if input[index] == CR && input[index + 1] == LF {
         return Ok((&input[..index], &input[index + 2..]));
     } else {
         return Err(RespError::IncorrectFormat);
@@ -159,6 +342,9 @@ pub fn parse_integers(input: &[u8]) -> RespResult {
 }
 
 pub fn parse_bulk_strings(input: &[u8]) -> RespResult {
+    if let Some(leftover) = input.strip_prefix(b"?\r\n") {
+        return parse_streamed_string_chunks(leftover);
+    }
     let (size_str, leftover) = parse_everything_until_crlf(input)?;
     let size = std::str::from_utf8(size_str)?.parse::<i64>()?;
 
@@ -172,6 +358,10 @@ pub fn parse_bulk_strings(input: &[u8]) -> RespResult {
 }
 
 pub fn parse_arrays(input: &[u8]) -> RespResult {
+    if let Some(leftover) = input.strip_prefix(b"?\r\n") {
+        let (result, left) = parse_streamed_elements(leftover)?;
+        return Ok((Resp::Array(result), left));
+    }
     let (size_str, leftover) = parse_everything_until_crlf(input)?;
     let size = std::str::from_utf8(size_str)?.parse::<i64>()?;
 
@@ -180,7 +370,7 @@ pub fn parse_arrays(input: &[u8]) -> RespResult {
     } else {
         let sizes = size as usize;
         let mut left = leftover;
-        let mut result = Vec::with_capacity(sizes);
+        let mut result = Vec::with_capacity(sizes.min(leftover.len()));
         for _ in 0..sizes {
             let (element, tmp) = parse_resp(left)?;
             result.push(element);
@@ -190,6 +380,161 @@ pub fn parse_arrays(input: &[u8]) -> RespResult {
     }
 }
 
+pub fn parse_maps(input: &[u8]) -> RespResult<'_> {
+    let (elements, left) = if let Some(leftover) = input.strip_prefix(b"?\r\n") {
+        parse_streamed_elements(leftover)?
+    } else {
+        let (size, leftover) = parse_aggregate_size(input)?;
+        let size = size.checked_mul(2).ok_or(RespError::IncorrectFormat)?;
+        parse_elements(leftover, size)?
+    };
+    Ok((Resp::Map(into_pairs(elements)?), left))
+}
+
+pub fn parse_pushes(input: &[u8]) -> RespResult<'_> {
+    let (size, leftover) = parse_aggregate_size(input)?;
+    let (result, left) = parse_elements(leftover, size)?;
+    Ok((Resp::Push(result), left))
+}
+
+/// Parses an attribute map along with the value that follows it.
+pub fn parse_attributes(input: &[u8]) -> RespResult<'_> {
+    let (size, leftover) = parse_aggregate_size(input)?;
+    let size = size.checked_mul(2).ok_or(RespError::IncorrectFormat)?;
+    let (elements, leftover) = parse_elements(leftover, size)?;
+    let (value, leftover) = parse_resp(leftover)?;
+    let resp = Resp::WithAttributes {
+        attrs: into_pairs(elements)?,
+        value: Box::new(value),
+    };
+    Ok((resp, leftover))
+}
+
+pub fn parse_sets(input: &[u8]) -> RespResult<'_> {
+    let (result, left) = if let Some(leftover) = input.strip_prefix(b"?\r\n") {
+        parse_streamed_elements(leftover)?
+    } else {
+        let (size, leftover) = parse_aggregate_size(input)?;
+        parse_elements(leftover, size)?
+    };
+    Ok((Resp::Set(result), left))
+}
+
+pub fn parse_null(input: &[u8]) -> RespResult<'_> {
+    match parse_everything_until_crlf(input)? {
+        (b"", leftover) => Ok((Resp::Null, leftover)),
+        _ => Err(RespError::IncorrectFormat),
+    }
+}
+
+pub fn parse_booleans(input: &[u8]) -> RespResult<'_> {
+    match parse_everything_until_crlf(input)? {
+        (b"t", leftover) => Ok((Resp::Boolean(true), leftover)),
+        (b"f", leftover) => Ok((Resp::Boolean(false), leftover)),
+        _ => Err(RespError::IncorrectFormat),
+    }
+}
+
+pub fn parse_doubles(input: &[u8]) -> RespResult<'_> {
+    parse_everything_until_crlf(input).map(|(x, y)| (Resp::Double(x), y))
+}
+
+pub fn parse_big_numbers(input: &[u8]) -> RespResult<'_> {
+    parse_everything_until_crlf(input).map(|(x, y)| (Resp::BigNumber(x), y))
+}
+
+pub fn parse_blob_errors(input: &[u8]) -> RespResult<'_> {
+    let (size, leftover) = parse_aggregate_size(input)?;
+    let (result, leftover) = parse_everything_until_index(leftover, size)?;
+    Ok((Resp::BlobError(result), leftover))
+}
+
+pub fn parse_verbatim_strings(input: &[u8]) -> RespResult<'_> {
+    let (size, leftover) = parse_aggregate_size(input)?;
+    let (result, leftover) = parse_everything_until_index(leftover, size)?;
+    if result.len() < 4 || result[3] != b':' {
+        return Err(RespError::IncorrectFormat);
+    }
+    let resp = Resp::Verbatim {
+        format: &result[..3],
+        text: &result[4..],
+    };
+    Ok((resp, leftover))
+}
+
+// RESP3 aggregates do not have a nil form, so a negative size is rejected.
+fn parse_aggregate_size(input: &[u8]) -> Result<(usize, &[u8]), RespError> {
+    let (size_str, leftover) = parse_everything_until_crlf(input)?;
+    let size = std::str::from_utf8(size_str)?.parse::<i64>()?;
+    if size < 0 {
+        return Err(RespError::IncorrectFormat);
+    }
+    Ok((size as usize, leftover))
+}
+
+fn into_pairs(elements: Vec<Resp<'_>>) -> Result<Vec<(Resp<'_>, Resp<'_>)>, RespError> {
+    if elements.len() % 2 == 1 {
+        return Err(RespError::IncorrectFormat);
+    }
+    let mut result = Vec::with_capacity(elements.len() / 2);
+    let mut elements = elements.into_iter();
+    while let (Some(key), Some(value)) = (elements.next(), elements.next()) {
+        result.push((key, value));
+    }
+    Ok(result)
+}
+
+type ElementsResult<'a> = Result<(Vec<Resp<'a>>, &'a [u8]), RespError>;
+
+fn parse_elements(input: &[u8], size: usize) -> ElementsResult<'_> {
+    let mut left = input;
+    // Every element takes at least a byte, so a bogus size cannot preallocate much
+    let mut result = Vec::with_capacity(size.min(input.len()));
+    for _ in 0..size {
+        let (element, tmp) = parse_resp(left)?;
+        result.push(element);
+        left = tmp;
+    }
+    Ok((result, left))
+}
+
+// Elements of a streamed aggregate, up to and including the `.\r\n` terminator.
+fn parse_streamed_elements(input: &[u8]) -> ElementsResult<'_> {
+    let mut left = input;
+    let mut result = Vec::new();
+    loop {
+        match left {
+            [b'.', CR, LF, tmp @ ..] => return Ok((result, tmp)),
+            [] | [b'.'] | [b'.', CR] => return Err(RespError::NotEnoughBytes),
+            _ => {
+                let (element, tmp) = parse_resp(left)?;
+                result.push(element);
+                left = tmp;
+            }
+        }
+    }
+}
+
+// Chunks of a streamed string, up to and including the `;0\r\n` terminator.
+fn parse_streamed_string_chunks(input: &[u8]) -> RespResult<'_> {
+    let mut left = input;
+    let mut chunks = Vec::new();
+    loop {
+        if left.is_empty() {
+            return Err(RespError::NotEnoughBytes);
+        } else if left[0] != b';' {
+            return Err(RespError::IncorrectFormat);
+        }
+        let (size, tmp) = parse_aggregate_size(&left[1..])?;
+        if size == 0 {
+            return Ok((Resp::StreamedString(chunks), tmp));
+        }
+        let (chunk, tmp) = parse_everything_until_index(tmp, size)?;
+        chunks.push(chunk);
+        left = tmp;
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -245,6 +590,18 @@ mod test {
         assert!(left.is_empty());
     }
 
+    #[test]
+    pub fn test_incomplete_bulk_string() {
+        for input in [&b"$3\r\nfoo"[..], b"$3\r\nfoo\r", b"$3\r\nfo"] {
+            let err = parse_resp(input).unwrap_err();
+            assert!(matches!(err, RespError::NotEnoughBytes));
+        }
+        for input in [&b"$3\r\nfoox"[..], b"$3\r\nfoo\rx"] {
+            let err = parse_resp(input).unwrap_err();
+            assert!(matches!(err, RespError::IncorrectFormat));
+        }
+    }
+
     #[test]
     pub fn test_arrays() {
         let input = "*2\r\n$3\r\nfoo\r\n$3\r\nbar\r\n".as_bytes();
@@ -293,6 +650,156 @@ mod test {
         assert!(left.is_empty());
     }
 
+    #[test]
+    pub fn test_write_to_writer() {
+        let input = b"*3\r\n*2\r\n:1\r\n$-1\r\n+Foo\r\n*-1\r\n";
+        let (resp, _) = parse_resp(input).unwrap();
+        let mut output = Vec::new();
+        resp.write_to_writer(&mut output).unwrap();
+        assert_eq!(output, input.to_vec());
+    }
+
+    #[test]
+    pub fn test_resp3_types() {
+        let input = b"%2\r\n+first\r\n#t\r\n$6\r\nsecond\r\n~3\r\n_\r\n,3.14\r\n(3492890328409238509324850943850943825024385\r\n";
+        let (resp, left) = parse_resp(input).unwrap();
+        assert_eq!(
+            resp,
+            Resp::Map(vec![
+                (Resp::String(b"first"), Resp::Boolean(true)),
+                (
+                    Resp::BulkString(b"second"),
+                    Resp::Set(vec![
+                        Resp::Null,
+                        Resp::Double(b"3.14"),
+                        Resp::BigNumber(b"3492890328409238509324850943850943825024385"),
+                    ])
+                ),
+            ])
+        );
+        assert!(left.is_empty());
+        let input = b"*2\r\n!21\r\nSYNTAX invalid syntax\r\n=15\r\ntxt:Some string\r\n";
+        let (resp, left) = parse_resp(input).unwrap();
+        assert_eq!(
+            resp,
+            Resp::Array(vec![
+                Resp::BlobError(b"SYNTAX invalid syntax"),
+                Resp::Verbatim {
+                    format: b"txt",
+                    text: b"Some string"
+                },
+            ])
+        );
+        assert!(left.is_empty());
+        let err = parse_resp(b"#x\r\n").unwrap_err();
+        assert!(matches!(err, RespError::IncorrectFormat));
+        let err = parse_resp(b"%-1\r\n").unwrap_err();
+        assert!(matches!(err, RespError::IncorrectFormat));
+    }
+
+    #[test]
+    pub fn test_streamed_types() {
+        let input = b"$?\r\n;4\r\nHell\r\n;5\r\no wor\r\n;1\r\nd\r\n;0\r\n";
+        let (resp, left) = parse_resp(input).unwrap();
+        assert_eq!(resp, Resp::StreamedString(vec![b"Hell", b"o wor", b"d"]));
+        assert!(left.is_empty());
+        let input = b"*?\r\n:1\r\n~?\r\n+a\r\n.\r\n%?\r\n+key\r\n:2\r\n.\r\n.\r\n";
+        let (resp, left) = parse_resp(input).unwrap();
+        assert_eq!(
+            resp,
+            Resp::Array(vec![
+                Resp::Integer(b"1"),
+                Resp::Set(vec![Resp::String(b"a")]),
+                Resp::Map(vec![(Resp::String(b"key"), Resp::Integer(b"2"))]),
+            ])
+        );
+        assert!(left.is_empty());
+        let err = parse_resp(b"*?\r\n:1\r\n.").unwrap_err();
+        assert!(matches!(err, RespError::NotEnoughBytes));
+        let err = parse_resp(b"$?\r\n;4\r\nHell\r\n").unwrap_err();
+        assert!(matches!(err, RespError::NotEnoughBytes));
+        let err = parse_resp(b"%?\r\n+key\r\n.\r\n").unwrap_err();
+        assert!(matches!(err, RespError::IncorrectFormat));
+    }
+
+    #[test]
+    pub fn test_huge_aggregate_sizes() {
+        let err = parse_resp(b"%4611686018427387904\r\n").unwrap_err();
+        assert!(matches!(err, RespError::NotEnoughBytes));
+        let err = parse_resp(b"|9223372036854775807\r\n+a\r\n").unwrap_err();
+        assert!(matches!(err, RespError::NotEnoughBytes));
+        let err = parse_resp(b"*9223372036854775807\r\n:1\r\n").unwrap_err();
+        assert!(matches!(err, RespError::NotEnoughBytes));
+        let err = parse_resp(b"~9223372036854775807\r\n").unwrap_err();
+        assert!(matches!(err, RespError::NotEnoughBytes));
+    }
+
+    #[test]
+    pub fn test_write_resp3_types() {
+        let input = b"%1\r\n=15\r\ntxt:Some string\r\n~4\r\n_\r\n#f\r\n,inf\r\n!3\r\nERR\r\n";
+        let (resp, _) = parse_resp(input).unwrap();
+        let mut output = Vec::new();
+        resp.write_to_writer(&mut output).unwrap();
+        assert_eq!(output, input.to_vec());
+        let mut output = Vec::new();
+        Resp::StreamedString(vec![b"Hell", b"", b"o"])
+            .write_to_writer(&mut output)
+            .unwrap();
+        assert_eq!(output, b"$?\r\n;4\r\nHell\r\n;1\r\no\r\n;0\r\n".to_vec());
+    }
+
+    #[test]
+    pub fn test_attributes() {
+        let input = b"*2\r\n|1\r\n+key-popularity\r\n%2\r\n$1\r\na\r\n,0.1923\r\n$1\r\nb\r\n,0.0012\r\n:2039123\r\n:9543892\r\n";
+        let (resp, left) = parse_resp(input).unwrap();
+        assert_eq!(
+            resp,
+            Resp::Array(vec![
+                Resp::WithAttributes {
+                    attrs: vec![(
+                        Resp::String(b"key-popularity"),
+                        Resp::Map(vec![
+                            (Resp::BulkString(b"a"), Resp::Double(b"0.1923")),
+                            (Resp::BulkString(b"b"), Resp::Double(b"0.0012")),
+                        ])
+                    )],
+                    value: Box::new(Resp::Integer(b"2039123")),
+                },
+                Resp::Integer(b"9543892"),
+            ])
+        );
+        assert!(left.is_empty());
+        let mut output = Vec::new();
+        resp.write_to_writer(&mut output).unwrap();
+        assert_eq!(output, input.to_vec());
+        let (resp, left) = parse_resp_without_attributes(input).unwrap();
+        assert_eq!(
+            resp,
+            Resp::Array(vec![Resp::Integer(b"2039123"), Resp::Integer(b"9543892")])
+        );
+        assert!(left.is_empty());
+        let err = parse_resp(b"|1\r\n+a\r\n+b\r\n").unwrap_err();
+        assert!(matches!(err, RespError::NotEnoughBytes));
+    }
+
+    #[test]
+    pub fn test_push() {
+        let input = b">3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$5\r\nhello\r\n";
+        let (resp, left) = parse_resp(input).unwrap();
+        assert_eq!(
+            resp,
+            Resp::Push(vec![
+                Resp::BulkString(b"message"),
+                Resp::BulkString(b"news"),
+                Resp::BulkString(b"hello"),
+            ])
+        );
+        assert!(left.is_empty());
+        let mut output = Vec::new();
+        resp.write_to_writer(&mut output).unwrap();
+        assert_eq!(output, input.to_vec());
+    }
+
     #[test]
     pub fn test_info_command_output() {
         let input = b"$5180\r\n# Server\r\nredis_version:255.255.255\r\nredis_git_sha1:f36eb5a1\r\nredis_git_dirty:0\r\nredis_build_id:f219bc9a3885f906\r\nredis_mode:standalone\r\nos:Linux 5.15.0-53-generic x86_64\r\narch_bits:64\r\nmonotonic_clock:POSIX clock_gettime\r\nmultiplexing_api:epoll\r\natomicvar_api:c11-builtin\r\ngcc_version:11.3.0\r\nprocess_id:44314\r\nprocess_supervised:no\r\nrun_id:91b15383dedb3acb3991ee89c50dc2e3ea637986\r\ntcp_port:6379\r\nserver_time_usec:1669247775474011\r\nuptime_in_seconds:32726\r\nuptime_in_days:0\r\nhz:10\r\nconfigured_hz:10\r\nlru_clock:8303391\r\nexecutable:/home/hbina/git/redis/./src/redis-server\r\nconfig_file:/home/hbina/git/redis/./redis.conf\r\nio_threads_active:0\r\nlistener0:name=tcp,bind=127.0.0.1,bind=-::1,port=6379\r\n\r\n# Clients\r\nconnected_clients:1\r\ncluster_connections:0\r\nmaxclients:10000\r\nclient_recent_max_input_buffer:8\r\nclient_recent_max_output_buffer:0\r\nblocked_clients:0\r\ntracking_clients:0\r\nclients_in_timeout_table:0\r\n\r\n# Memory\r\nused_memory:1063504\r\nused_memory_human:1.01M\r\nused_memory_rss:8257536\r\nused_memory_rss_human:7.88M\r\nused_memory_peak:1236840\r\nused_memory_peak_human:1.18M\r\nused_memory_peak_perc:85.99%\r\nused_memory_overhead:867224\r\nused_memory_startup:865168\r\nused_memory_dataset:196280\r\nused_memory_dataset_perc:98.96%\r\nallocator_allocated:1341384\r\nallocator_active:1740800\r\nallocator_resident:6275072\r\ntotal_system_memory:33048694784\r\ntotal_system_memory_human:30.78G\r\nused_memory_lua:31744\r\nused_memory_vm_eval:31744\r\nused_memory_lua_human:31.00K\r\nused_memory_scripts_eval:0\r\nnumber_of_cached_scripts:0\r\nnumber_of_functions:0\r\nnumber_of_libraries:0\r\nused_memory_vm_functions:32768\r\nused_memory_vm_total:64512\r\nused_memory_vm_total_human:63.00K\r\nused_memory_functions:184\r\nused_memory_scripts:184\r\nused_memory_scripts_human:184B\r\nmaxmemory:0\r\nmaxmemory_human:0B\r\nmaxmemory_policy:noeviction\r\nallocator_frag_ratio:1.30\r\nallocator_frag_bytes:399416\r\nallocator_rss_ratio:3.60\r\nallocator_rss_bytes:4534272\r\nrss_overhead_ratio:1.32\r\nrss_overhead_bytes:1982464\r\nmem_fragmentation_ratio:7.93\r\nmem_fragmentation_bytes:7216328\r\nmem_not_counted_for_evict:0\r\nmem_replication_backlog:0\r\nmem_total_replication_buffers:0\r\nmem_clients_slaves:0\r\nmem_clients_normal:1800\r\nmem_cluster_links:0\r\nmem_aof_buffer:0\r\nmem_allocator:jemalloc-5.2.1\r\nactive_defrag_running:0\r\nlazyfree_pending_objects:0\r\nlazyfreed_objects:0\r\n\r\n# Persistence\r\nloading:0\r\nasync_loading:0\r\ncurrent_cow_peak:0\r\ncurrent_cow_size:0\r\ncurrent_cow_size_age:0\r\ncurrent_fork_perc:0.00\r\ncurrent_save_keys_processed:0\r\ncurrent_save_keys_total:0\r\nrdb_changes_since_last_save:0\r\nrdb_bgsave_in_progress:0\r\nrdb_last_save_time:1669247076\r\nrdb_last_bgsave_status:ok\r\nrdb_last_bgsave_time_sec:0\r\nrdb_current_bgsave_time_sec:-1\r\nrdb_saves:1\r\nrdb_last_cow_size:225280\r\nrdb_last_load_keys_expired:0\r\nrdb_last_load_keys_loaded:0\r\naof_enabled:0\r\naof_rewrite_in_progress:0\r\naof_rewrite_scheduled:0\r\naof_last_rewrite_time_sec:-1\r\naof_current_rewrite_time_sec:-1\r\naof_last_bgrewrite_status:ok\r\naof_rewrites:0\r\naof_rewrites_consecutive_failures:0\r\naof_last_write_status:ok\r\naof_last_cow_size:0\r\nmodule_fork_in_progress:0\r\nmodule_fork_last_cow_size:0\r\n\r\n# Stats\r\ntotal_connections_received:13\r\ntotal_commands_processed:21\r\ninstantaneous_ops_per_sec:0\r\ntotal_net_input_bytes:431\r\ntotal_net_output_bytes:1136345\r\ntotal_net_repl_input_bytes:0\r\ntotal_net_repl_output_bytes:0\r\ninstantaneous_input_kbps:0.00\r\ninstantaneous_output_kbps:0.00\r\ninstantaneous_input_repl_kbps:0.00\r\ninstantaneous_output_repl_kbps:0.00\r\nrejected_connections:0\r\nsync_full:0\r\nsync_partial_ok:0\r\nsync_partial_err:0\r\nexpired_keys:0\r\nexpired_stale_perc:0.00\r\nexpired_time_cap_reached_count:0\r\nexpire_cycle_cpu_milliseconds:1046\r\nevicted_keys:0\r\nevicted_clients:0\r\ntotal_eviction_exceeded_time:0\r\ncurrent_eviction_exceeded_time:0\r\nkeyspace_hits:0\r\nkeyspace_misses:0\r\npubsub_channels:0\r\npubsub_patterns:0\r\npubsubshard_channels:0\r\nlatest_fork_usec:295\r\ntotal_forks:1\r\nmigrate_cached_sockets:0\r\nslave_expires_tracked_keys:0\r\nactive_defrag_hits:0\r\nactive_defrag_misses:0\r\nactive_defrag_key_hits:0\r\nactive_defrag_key_misses:0\r\ntotal_active_defrag_time:0\r\ncurrent_active_defrag_time:0\r\ntracking_total_keys:0\r\ntracking_total_items:0\r\ntracking_total_prefixes:0\r\nunexpected_error_replies:0\r\ntotal_error_replies:1\r\ndump_payload_sanitizations:0\r\ntotal_reads_processed:35\r\ntotal_writes_processed:33\r\nio_threaded_reads_processed:0\r\nio_threaded_writes_processed:0\r\nreply_buffer_shrinks:23\r\nreply_buffer_expands:10\r\nacl_access_denied_auth:0\r\nacl_access_denied_cmd:0\r\nacl_access_denied_key:0\r\nacl_access_denied_channel:0\r\n\r\n# Replication\r\nrole:master\r\nconnected_slaves:0\r\nmaster_failover_state:no-failover\r\nmaster_replid:b47d5da0e4b42b52640f5e086a4b24d4a6cb6c5f\r\nmaster_replid2:0000000000000000000000000000000000000000\r\nmaster_repl_offset:0\r\nsecond_repl_offset:-1\r\nrepl_backlog_active:0\r\nrepl_backlog_size:1048576\r\nrepl_backlog_first_byte_offset:0\r\nrepl_backlog_histlen:0\r\n\r\n# CPU\r\nused_cpu_sys:39.159292\r\nused_cpu_user:24.101233\r\nused_cpu_sys_children:0.000000\r\nused_cpu_user_children:0.002011\r\nused_cpu_sys_main_thread:39.154828\r\nused_cpu_user_main_thread:24.102692\r\n\r\n# Modules\r\n\r\n# Errorstats\r\nerrorstat_ERR:count=1\r\n\r\n# Cluster\r\ncluster_enabled:0\r\n\r\n# Keyspace\r\ndb0:keys=1,expires=0,avg_ttl=0\r\n\r\n";
@@ -0,0 +1,195 @@
+//! A scripted stand-in for Redis, to test client code without a Redis server.
+//!
+//! Commands are expected in the order they are scripted, whatever connection they come from,
+//! and compared byte for byte with what the client sent.
+
+use crate::server::{serve_connection, Command, Handler};
+use crate::{OwnedResp, Resp, RespError};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Debug, Default)]
+struct State {
+    expected: VecDeque<(Vec<Vec<u8>>, OwnedResp)>,
+    received: Vec<Command>,
+}
+
+/// Serves scripted replies on a local port until dropped.
+#[derive(Debug)]
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    stopped: Arc<AtomicBool>,
+}
+
+struct MockHandler {
+    state: Arc<Mutex<State>>,
+    reply: OwnedResp,
+}
+
+impl Handler for MockHandler {
+    fn call<'a>(&'a mut self, cmd: &'a Command) -> Resp<'a> {
+        let mut state = self.state.lock().unwrap();
+        state.received.push(cmd.clone());
+        let expected = match state.expected.front() {
+            Some((args, _)) => args.split_first(),
+            None => None,
+        };
+        self.reply = match expected {
+            Some((name, args)) if name == cmd.name() && args == cmd.args() => {
+                state.expected.pop_front().unwrap().1
+            }
+            // A mismatch leaves the expectation in place so that `assert_done` reports it
+            _ => OwnedResp::Error(
+                format!(
+                    "ERR mock: unexpected command '{}'",
+                    String::from_utf8_lossy(cmd.name())
+                )
+                .into_bytes(),
+            ),
+        };
+        self.reply.as_resp()
+    }
+}
+
+impl MockServer {
+    /// Binds a free port on the loopback interface and starts accepting connections.
+    pub fn start() -> Result<Self, RespError> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let server = Self {
+            addr: listener.local_addr()?,
+            state: Arc::default(),
+            stopped: Arc::default(),
+        };
+        let state = server.state.clone();
+        let stopped = server.stopped.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                let mut handler = MockHandler {
+                    state: state.clone(),
+                    reply: OwnedResp::Null,
+                };
+                if let Ok(stream) = stream {
+                    thread::spawn(move || {
+                        let _ = serve_connection(stream, &mut handler);
+                    });
+                }
+            }
+        });
+        Ok(server)
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Scripts the next command, to be completed with [`Expectation::reply`].
+    pub fn expect<A: AsRef<[u8]>>(&self, args: &[A]) -> Expectation<'_> {
+        Expectation {
+            server: self,
+            args: args.iter().map(|a| a.as_ref().to_vec()).collect(),
+        }
+    }
+
+    /// Serves a single connection on the current thread, e.g. one end of an in-memory pipe.
+    pub fn serve<S: Read + Write>(&self, stream: S) -> Result<(), RespError> {
+        let mut handler = MockHandler {
+            state: self.state.clone(),
+            reply: OwnedResp::Null,
+        };
+        serve_connection(stream, &mut handler)
+    }
+
+    /// Every command received so far, expected or not.
+    pub fn received(&self) -> Vec<Command> {
+        self.state.lock().unwrap().received.clone()
+    }
+
+    /// Panics if some scripted commands have not been received.
+    pub fn assert_done(&self) {
+        let state = self.state.lock().unwrap();
+        if let Some((args, _)) = state.expected.front() {
+            let args: Vec<_> = args.iter().map(|a| String::from_utf8_lossy(a)).collect();
+            panic!(
+                "{} expected commands not received, starting with {:?}",
+                state.expected.len(),
+                args
+            );
+        }
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wake the accept loop up so that it sees the flag
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+/// A scripted command waiting for its reply.
+#[derive(Debug)]
+#[must_use = "the expectation is only registered by `reply`"]
+pub struct Expectation<'m> {
+    server: &'m MockServer,
+    args: Vec<Vec<u8>>,
+}
+
+impl Expectation<'_> {
+    pub fn reply(self, reply: Resp<'_>) {
+        let mut state = self.server.state.lock().unwrap();
+        state
+            .expected
+            .push_back((self.args, OwnedResp::from(reply)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::Client;
+
+    #[test]
+    pub fn test_scripted_replies() {
+        let server = MockServer::start().unwrap();
+        server.expect(&["GET", "k"]).reply(Resp::NilBulk);
+        server.expect(&["SET", "k", "v"]).reply(Resp::String(b"OK"));
+        server.expect(&["GET", "k"]).reply(Resp::BulkString(b"v"));
+        let mut client = Client::connect(server.addr()).unwrap();
+        assert_eq!(client.send(&["GET", "k"]).unwrap(), OwnedResp::NilBulk);
+        let replies = client
+            .pipeline()
+            .cmd(&["SET", "k", "v"])
+            .cmd(&["GET", "k"])
+            .execute()
+            .unwrap();
+        assert_eq!(
+            replies,
+            vec![
+                OwnedResp::String(b"OK".to_vec()),
+                OwnedResp::BulkString(b"v".to_vec())
+            ]
+        );
+        server.assert_done();
+        assert_eq!(server.received().len(), 3);
+        assert!(server.received()[1].is("set"));
+    }
+
+    #[test]
+    pub fn test_unexpected_command() {
+        let server = MockServer::start().unwrap();
+        server.expect(&["GET", "k"]).reply(Resp::NilBulk);
+        let mut client = Client::connect(server.addr()).unwrap();
+        let reply = client.send(&["get", "k"]).unwrap();
+        assert!(matches!(reply, OwnedResp::Error(_)));
+        let done = std::panic::catch_unwind(|| server.assert_done());
+        assert!(done.is_err());
+    }
+}
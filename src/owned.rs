@@ -0,0 +1,146 @@
+use crate::{Resp, RespError};
+use std::io::Write;
+
+/// Owned counterpart of [`Resp`], for frames that need to outlive the buffer they were parsed
+/// from (e.g. to be sent to another thread).
+///
+/// [`OwnedResp::as_resp`] borrows it back as a [`Resp`], so everything working on a `Resp`
+/// works on an `OwnedResp` as well.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum OwnedResp {
+    String(Vec<u8>),
+    Error(Vec<u8>),
+    Integer(Vec<u8>),
+    BulkString(Vec<u8>),
+    NilBulk,
+    Array(Vec<OwnedResp>),
+    NilArray,
+    Null,
+    Boolean(bool),
+    Double(Vec<u8>),
+    BigNumber(Vec<u8>),
+    BlobError(Vec<u8>),
+    Verbatim {
+        format: Vec<u8>,
+        text: Vec<u8>,
+    },
+    Map(Vec<(OwnedResp, OwnedResp)>),
+    Set(Vec<OwnedResp>),
+    StreamedString(Vec<Vec<u8>>),
+    WithAttributes {
+        attrs: Vec<(OwnedResp, OwnedResp)>,
+        value: Box<OwnedResp>,
+    },
+    Push(Vec<OwnedResp>),
+}
+
+fn owned_pairs(pairs: &[(Resp, Resp)]) -> Vec<(OwnedResp, OwnedResp)> {
+    pairs
+        .iter()
+        .map(|(k, v)| (OwnedResp::from(k), OwnedResp::from(v)))
+        .collect()
+}
+
+fn borrowed_pairs(pairs: &[(OwnedResp, OwnedResp)]) -> Vec<(Resp<'_>, Resp<'_>)> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.as_resp(), v.as_resp()))
+        .collect()
+}
+
+impl OwnedResp {
+    pub fn as_resp(&self) -> Resp<'_> {
+        match self {
+            OwnedResp::String(s) => Resp::String(s),
+            OwnedResp::Error(s) => Resp::Error(s),
+            OwnedResp::Integer(s) => Resp::Integer(s),
+            OwnedResp::BulkString(s) => Resp::BulkString(s),
+            OwnedResp::NilBulk => Resp::NilBulk,
+            OwnedResp::Array(a) => Resp::Array(a.iter().map(OwnedResp::as_resp).collect()),
+            OwnedResp::NilArray => Resp::NilArray,
+            OwnedResp::Null => Resp::Null,
+            OwnedResp::Boolean(b) => Resp::Boolean(*b),
+            OwnedResp::Double(s) => Resp::Double(s),
+            OwnedResp::BigNumber(s) => Resp::BigNumber(s),
+            OwnedResp::BlobError(s) => Resp::BlobError(s),
+            OwnedResp::Verbatim { format, text } => Resp::Verbatim { format, text },
+            OwnedResp::Map(m) => Resp::Map(borrowed_pairs(m)),
+            OwnedResp::Set(a) => Resp::Set(a.iter().map(OwnedResp::as_resp).collect()),
+            OwnedResp::StreamedString(c) => {
+                Resp::StreamedString(c.iter().map(Vec::as_slice).collect())
+            }
+            OwnedResp::WithAttributes { attrs, value } => Resp::WithAttributes {
+                attrs: borrowed_pairs(attrs),
+                value: Box::new(value.as_resp()),
+            },
+            OwnedResp::Push(a) => Resp::Push(a.iter().map(OwnedResp::as_resp).collect()),
+        }
+    }
+
+    pub fn write_to_writer<W>(&self, writer: &mut W) -> Result<(), RespError>
+    where
+        W: Write,
+    {
+        self.as_resp().write_to_writer(writer)
+    }
+}
+
+impl From<&Resp<'_>> for OwnedResp {
+    fn from(from: &Resp<'_>) -> Self {
+        match from {
+            Resp::String(s) => OwnedResp::String(s.to_vec()),
+            Resp::Error(s) => OwnedResp::Error(s.to_vec()),
+            Resp::Integer(s) => OwnedResp::Integer(s.to_vec()),
+            Resp::BulkString(s) => OwnedResp::BulkString(s.to_vec()),
+            Resp::NilBulk => OwnedResp::NilBulk,
+            Resp::Array(a) => OwnedResp::Array(a.iter().map(OwnedResp::from).collect()),
+            Resp::NilArray => OwnedResp::NilArray,
+            Resp::Null => OwnedResp::Null,
+            Resp::Boolean(b) => OwnedResp::Boolean(*b),
+            Resp::Double(s) => OwnedResp::Double(s.to_vec()),
+            Resp::BigNumber(s) => OwnedResp::BigNumber(s.to_vec()),
+            Resp::BlobError(s) => OwnedResp::BlobError(s.to_vec()),
+            Resp::Verbatim { format, text } => OwnedResp::Verbatim {
+                format: format.to_vec(),
+                text: text.to_vec(),
+            },
+            Resp::Map(m) => OwnedResp::Map(owned_pairs(m)),
+            Resp::Set(a) => OwnedResp::Set(a.iter().map(OwnedResp::from).collect()),
+            Resp::StreamedString(c) => {
+                OwnedResp::StreamedString(c.iter().map(|s| s.to_vec()).collect())
+            }
+            Resp::WithAttributes { attrs, value } => OwnedResp::WithAttributes {
+                attrs: owned_pairs(attrs),
+                value: Box::new(OwnedResp::from(value.as_ref())),
+            },
+            Resp::Push(a) => OwnedResp::Push(a.iter().map(OwnedResp::from).collect()),
+        }
+    }
+}
+
+impl From<Resp<'_>> for OwnedResp {
+    fn from(from: Resp<'_>) -> Self {
+        OwnedResp::from(&from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_resp;
+
+    #[test]
+    pub fn test_owned_round_trip() {
+        let input = b"*3\r\n|1\r\n+ttl\r\n:10\r\n$3\r\nfoo\r\n%1\r\n=7\r\ntxt:bar\r\n~1\r\n_\r\n>1\r\n#t\r\n";
+        let owned = {
+            let buffer = input.to_vec();
+            let (resp, _) = parse_resp(&buffer).unwrap();
+            OwnedResp::from(resp)
+        };
+        let (resp, _) = parse_resp(input).unwrap();
+        assert_eq!(owned.as_resp(), resp);
+        let mut output = Vec::new();
+        owned.write_to_writer(&mut output).unwrap();
+        assert_eq!(output, input.to_vec());
+    }
+}
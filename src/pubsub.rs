@@ -0,0 +1,294 @@
+use crate::{Resp, RespError};
+
+/// A message received by a subscribed connection.
+///
+/// Works with both the RESP2 arrays and the RESP3 push frames. The unsubscribe
+/// confirmations carry no channel when the connection was not subscribed to anything.
+#[derive(Debug, Eq, PartialEq)]
+pub enum PubSubMessage<'a> {
+    Message {
+        channel: &'a [u8],
+        payload: &'a [u8],
+    },
+    PMessage {
+        pattern: &'a [u8],
+        channel: &'a [u8],
+        payload: &'a [u8],
+    },
+    SMessage {
+        channel: &'a [u8],
+        payload: &'a [u8],
+    },
+    Subscribe {
+        channel: &'a [u8],
+        count: i64,
+    },
+    Unsubscribe {
+        channel: Option<&'a [u8]>,
+        count: i64,
+    },
+    PSubscribe {
+        pattern: &'a [u8],
+        count: i64,
+    },
+    PUnsubscribe {
+        pattern: Option<&'a [u8]>,
+        count: i64,
+    },
+    SSubscribe {
+        channel: &'a [u8],
+        count: i64,
+    },
+    SUnsubscribe {
+        channel: Option<&'a [u8]>,
+        count: i64,
+    },
+    /// Reply to a `PING` sent while subscribed.
+    Pong(&'a [u8]),
+}
+
+fn bytes<'a>(resp: &Resp<'a>) -> Result<&'a [u8], RespError> {
+    resp.as_bytes().ok_or(RespError::IncorrectFormat)
+}
+
+fn optional_bytes<'a>(resp: &Resp<'a>) -> Result<Option<&'a [u8]>, RespError> {
+    match resp {
+        Resp::NilBulk | Resp::Null => Ok(None),
+        resp => bytes(resp).map(Some),
+    }
+}
+
+fn integer(resp: &Resp) -> Result<i64, RespError> {
+    resp.as_integer().ok_or(RespError::IncorrectFormat)
+}
+
+// The lowercased kind of a message and the elements following it.
+fn split_kind<'a, 'b>(from: &'b Resp<'a>) -> Result<(Vec<u8>, &'b [Resp<'a>]), RespError> {
+    match from {
+        Resp::Array(a) | Resp::Push(a) => match a.split_first() {
+            Some((kind, elements)) => Ok((bytes(kind)?.to_ascii_lowercase(), elements)),
+            None => Err(RespError::IncorrectFormat),
+        },
+        _ => Err(RespError::IncorrectFormat),
+    }
+}
+
+/// The channel and the payload of a `message`, which unlike with [`PubSubMessage::Message`]
+/// does not have to be a bulk string. Invalidations published over RESP2 carry an array of
+/// keys, see [`crate::tracking`].
+pub fn raw_message<'a, 'b>(from: &'b Resp<'a>) -> Result<(&'a [u8], &'b Resp<'a>), RespError> {
+    match split_kind(from)? {
+        (kind, [channel, payload]) if kind == b"message" => Ok((bytes(channel)?, payload)),
+        _ => Err(RespError::IncorrectFormat),
+    }
+}
+
+impl<'a, 'b> TryFrom<&'b Resp<'a>> for PubSubMessage<'a> {
+    type Error = RespError;
+
+    fn try_from(from: &'b Resp<'a>) -> Result<Self, Self::Error> {
+        let (kind, elements) = split_kind(from)?;
+        let message = match (kind.as_slice(), elements) {
+            (b"message", [channel, payload]) => PubSubMessage::Message {
+                channel: bytes(channel)?,
+                payload: bytes(payload)?,
+            },
+            (b"pmessage", [pattern, channel, payload]) => PubSubMessage::PMessage {
+                pattern: bytes(pattern)?,
+                channel: bytes(channel)?,
+                payload: bytes(payload)?,
+            },
+            (b"smessage", [channel, payload]) => PubSubMessage::SMessage {
+                channel: bytes(channel)?,
+                payload: bytes(payload)?,
+            },
+            (b"subscribe", [channel, count]) => PubSubMessage::Subscribe {
+                channel: bytes(channel)?,
+                count: integer(count)?,
+            },
+            (b"unsubscribe", [channel, count]) => PubSubMessage::Unsubscribe {
+                channel: optional_bytes(channel)?,
+                count: integer(count)?,
+            },
+            (b"psubscribe", [pattern, count]) => PubSubMessage::PSubscribe {
+                pattern: bytes(pattern)?,
+                count: integer(count)?,
+            },
+            (b"punsubscribe", [pattern, count]) => PubSubMessage::PUnsubscribe {
+                pattern: optional_bytes(pattern)?,
+                count: integer(count)?,
+            },
+            (b"ssubscribe", [channel, count]) => PubSubMessage::SSubscribe {
+                channel: bytes(channel)?,
+                count: integer(count)?,
+            },
+            (b"sunsubscribe", [channel, count]) => PubSubMessage::SUnsubscribe {
+                channel: optional_bytes(channel)?,
+                count: integer(count)?,
+            },
+            (b"pong", [payload]) => PubSubMessage::Pong(bytes(payload)?),
+            _ => return Err(RespError::IncorrectFormat),
+        };
+        Ok(message)
+    }
+}
+
+impl PubSubMessage<'_> {
+    /// Whether this confirms a change of subscriptions rather than carrying a message.
+    pub fn is_confirmation(&self) -> bool {
+        matches!(
+            self,
+            PubSubMessage::Subscribe { .. }
+                | PubSubMessage::Unsubscribe { .. }
+                | PubSubMessage::PSubscribe { .. }
+                | PubSubMessage::PUnsubscribe { .. }
+                | PubSubMessage::SSubscribe { .. }
+                | PubSubMessage::SUnsubscribe { .. }
+        )
+    }
+}
+
+/// How many confirmations the server sends for a subscription command.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Confirmations {
+    /// One per channel or pattern of the command.
+    Exactly(usize),
+    /// An unsubscribe from everything is confirmed once per current subscription (or once if
+    /// there is none), so the count cannot be known upfront. The count carried by the
+    /// confirmations drops to zero once no subscription is left.
+    UntilZeroCount,
+}
+
+/// Number of confirmations the server sends for a subscription command, or `None` for any
+/// other command.
+pub fn expected_confirmations<A: AsRef<[u8]>>(args: &[A]) -> Option<Confirmations> {
+    let (name, channels) = args.split_first()?;
+    match name.as_ref().to_ascii_lowercase().as_slice() {
+        // Without channels these are rejected with a single error
+        b"subscribe" | b"psubscribe" | b"ssubscribe" if !channels.is_empty() => {
+            Some(Confirmations::Exactly(channels.len()))
+        }
+        b"unsubscribe" | b"punsubscribe" | b"sunsubscribe" if channels.is_empty() => {
+            Some(Confirmations::UntilZeroCount)
+        }
+        b"unsubscribe" | b"punsubscribe" | b"sunsubscribe" => {
+            Some(Confirmations::Exactly(channels.len()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_resp;
+
+    fn classify(input: &[u8]) -> Result<PubSubMessage<'_>, RespError> {
+        let (resp, left) = parse_resp(input).unwrap();
+        assert!(left.is_empty());
+        PubSubMessage::try_from(&resp)
+    }
+
+    #[test]
+    pub fn test_messages() {
+        assert_eq!(
+            classify(b"*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$5\r\nhello\r\n").unwrap(),
+            PubSubMessage::Message {
+                channel: b"news",
+                payload: b"hello"
+            }
+        );
+        assert_eq!(
+            classify(b"*4\r\n$8\r\npmessage\r\n$2\r\nn*\r\n$4\r\nnews\r\n$0\r\n\r\n").unwrap(),
+            PubSubMessage::PMessage {
+                pattern: b"n*",
+                channel: b"news",
+                payload: b""
+            }
+        );
+        assert_eq!(
+            classify(b">3\r\n$8\r\nsmessage\r\n$1\r\nc\r\n$1\r\np\r\n").unwrap(),
+            PubSubMessage::SMessage {
+                channel: b"c",
+                payload: b"p"
+            }
+        );
+        assert_eq!(
+            classify(b"*2\r\n$4\r\npong\r\n$0\r\n\r\n").unwrap(),
+            PubSubMessage::Pong(b"")
+        );
+    }
+
+    #[test]
+    pub fn test_confirmations() {
+        assert_eq!(
+            classify(b"*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n").unwrap(),
+            PubSubMessage::Subscribe {
+                channel: b"news",
+                count: 1
+            }
+        );
+        assert_eq!(
+            classify(b"*3\r\n$11\r\nunsubscribe\r\n$-1\r\n:0\r\n").unwrap(),
+            PubSubMessage::Unsubscribe {
+                channel: None,
+                count: 0
+            }
+        );
+        assert_eq!(
+            classify(b">3\r\n$10\r\npsubscribe\r\n$2\r\nn*\r\n:2\r\n").unwrap(),
+            PubSubMessage::PSubscribe {
+                pattern: b"n*",
+                count: 2
+            }
+        );
+        assert_eq!(
+            classify(b"*3\r\n$12\r\nsunsubscribe\r\n$1\r\nc\r\n:0\r\n").unwrap(),
+            PubSubMessage::SUnsubscribe {
+                channel: Some(b"c"),
+                count: 0
+            }
+        );
+    }
+
+    #[test]
+    pub fn test_expected_confirmations() {
+        assert_eq!(
+            expected_confirmations(&["SUBSCRIBE", "a", "b"]),
+            Some(Confirmations::Exactly(2))
+        );
+        assert_eq!(
+            expected_confirmations(&["psubscribe", "n*"]),
+            Some(Confirmations::Exactly(1))
+        );
+        assert_eq!(expected_confirmations(&["SUBSCRIBE"]), None);
+        assert_eq!(
+            expected_confirmations(&["UNSUBSCRIBE"]),
+            Some(Confirmations::UntilZeroCount)
+        );
+        assert_eq!(
+            expected_confirmations(&["sunsubscribe", "a"]),
+            Some(Confirmations::Exactly(1))
+        );
+        assert_eq!(expected_confirmations(&["GET", "a"]), None);
+        assert_eq!(expected_confirmations::<&str>(&[]), None);
+        assert!(classify(b">3\r\n$9\r\nsubscribe\r\n$1\r\na\r\n:1\r\n")
+            .unwrap()
+            .is_confirmation());
+        assert!(!classify(b">3\r\n$7\r\nmessage\r\n$1\r\na\r\n$1\r\nb\r\n")
+            .unwrap()
+            .is_confirmation());
+    }
+
+    #[test]
+    pub fn test_not_a_message() {
+        let err = classify(b"*2\r\n$7\r\nmessage\r\n$4\r\nnews\r\n").unwrap_err();
+        assert!(matches!(err, RespError::IncorrectFormat));
+        let err = classify(b"*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n$1\r\n1\r\n").unwrap_err();
+        assert!(matches!(err, RespError::IncorrectFormat));
+        let err = classify(b"+OK\r\n").unwrap_err();
+        assert!(matches!(err, RespError::IncorrectFormat));
+        let err = classify(b"*0\r\n").unwrap_err();
+        assert!(matches!(err, RespError::IncorrectFormat));
+    }
+}
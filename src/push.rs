@@ -0,0 +1,232 @@
+use crate::pubsub::PubSubMessage;
+use crate::{OwnedResp, Resp, RespError};
+use std::collections::VecDeque;
+use std::sync::mpsc::Sender;
+
+/// Where a frame read from a RESP3 connection belongs.
+#[derive(Debug, PartialEq)]
+pub enum Routed<'a, T> {
+    /// The reply to the oldest outstanding request.
+    Reply(T, Resp<'a>),
+    /// A push frame (pubsub message, invalidation...), not related to any request.
+    Push(Resp<'a>),
+}
+
+/// Separates push frames from ordinary replies on a connection where both are interleaved.
+///
+/// Every request written to the connection is registered with
+/// [`Demultiplexer::expect_reply`], in the order it was sent. Since Redis replies in order,
+/// every frame that is not a push is then paired with the oldest outstanding request.
+///
+/// The subscription commands are the exception: in RESP3 they are confirmed by push frames,
+/// one per channel or pattern. They are registered with [`Demultiplexer::expect_confirmations`]
+/// instead, so that their confirmations are routed as replies. An `UNSUBSCRIBE` without
+/// channels cannot be registered since its number of confirmations is not known, and its
+/// confirmations are routed as pushes.
+#[derive(Debug)]
+pub struct Demultiplexer<T> {
+    // With the number of confirmations still expected after this one, for subscriptions
+    pending: VecDeque<(T, Option<usize>)>,
+}
+
+impl<T> Default for Demultiplexer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether the frame is a push, possibly preceded by attributes.
+pub fn is_push(frame: &Resp) -> bool {
+    match frame {
+        Resp::Push(_) => true,
+        Resp::WithAttributes { value, .. } => is_push(value),
+        _ => false,
+    }
+}
+
+/// Whether the frame confirms a change of subscriptions, as a RESP2 array or a RESP3 push.
+pub fn is_confirmation(frame: &Resp) -> bool {
+    match frame {
+        Resp::WithAttributes { value, .. } => is_confirmation(value),
+        frame => PubSubMessage::try_from(frame).is_ok_and(|m| m.is_confirmation()),
+    }
+}
+
+impl<T: Clone> Demultiplexer<T> {
+    /// Registers a subscription command, confirmed once for each of its `count` channels or
+    /// patterns. See [`crate::pubsub::expected_confirmations`].
+    pub fn expect_confirmations(&mut self, request: T, count: usize) {
+        for left in (0..count).rev() {
+            self.pending.push_back((request.clone(), Some(left)));
+        }
+    }
+}
+
+impl<T> Demultiplexer<T> {
+    pub fn new() -> Self {
+        Self {
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn expect_reply(&mut self, request: T) {
+        self.pending.push_back((request, None));
+    }
+
+    /// Number of replies still expected, counting every confirmation of a subscription.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Fails with [`RespError::IncorrectFormat`] if a reply arrives while no request is
+    /// outstanding.
+    pub fn route<'a>(&mut self, frame: Resp<'a>) -> Result<Routed<'a, T>, RespError> {
+        let confirmation = is_confirmation(&frame);
+        let expects_confirmation = matches!(self.pending.front(), Some((_, Some(_))));
+        if is_push(&frame) && !(confirmation && expects_confirmation) {
+            return Ok(Routed::Push(frame));
+        }
+        match self.pending.pop_front() {
+            Some((request, left)) => {
+                // An error reply stands for all the confirmations of the command
+                if let (Some(left), false) = (left, confirmation) {
+                    self.pending.drain(..left);
+                }
+                Ok(Routed::Reply(request, frame))
+            }
+            None => Err(RespError::IncorrectFormat),
+        }
+    }
+
+    /// Hands push frames over to `on_push` and returns replies along with their request.
+    pub fn route_with<'a, F>(
+        &mut self,
+        frame: Resp<'a>,
+        mut on_push: F,
+    ) -> Result<Option<(T, Resp<'a>)>, RespError>
+    where
+        F: FnMut(Resp<'a>),
+    {
+        match self.route(frame)? {
+            Routed::Reply(request, reply) => Ok(Some((request, reply))),
+            Routed::Push(push) => {
+                on_push(push);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Sends push frames over `pushes` and returns replies along with their request.
+    pub fn route_to<'a>(
+        &mut self,
+        frame: Resp<'a>,
+        pushes: &Sender<OwnedResp>,
+    ) -> Result<Option<(T, Resp<'a>)>, RespError> {
+        let mut result = Ok(());
+        let reply = self.route_with(frame, |push| {
+            result = pushes
+                .send(OwnedResp::from(push))
+                .map_err(|err| RespError::Other(Box::new(err)));
+        })?;
+        result.map(|_| reply)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_resp;
+
+    #[test]
+    pub fn test_route_interleaved_frames() {
+        let input = b"+OK\r\n>3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n:1\r\n";
+        let mut demux = Demultiplexer::new();
+        demux.expect_reply("SET");
+        demux.expect_reply("INCR");
+        let (frame, left) = parse_resp(input).unwrap();
+        assert_eq!(
+            demux.route(frame).unwrap(),
+            Routed::Reply("SET", Resp::String(b"OK"))
+        );
+        let (frame, left) = parse_resp(left).unwrap();
+        assert!(matches!(demux.route(frame).unwrap(), Routed::Push(_)));
+        assert_eq!(demux.pending(), 1);
+        let (frame, _) = parse_resp(left).unwrap();
+        assert_eq!(
+            demux.route(frame).unwrap(),
+            Routed::Reply("INCR", Resp::Integer(b"1"))
+        );
+        let err = demux.route(Resp::String(b"OK")).unwrap_err();
+        assert!(matches!(err, RespError::IncorrectFormat));
+    }
+
+    #[test]
+    pub fn test_route_confirmations() {
+        let input = b">3\r\n$9\r\nsubscribe\r\n$1\r\na\r\n:1\r\n>3\r\n$7\r\nmessage\r\n$1\r\na\r\n$2\r\nhi\r\n>3\r\n$9\r\nsubscribe\r\n$1\r\nb\r\n:2\r\n+PONG\r\n";
+        let mut demux = Demultiplexer::new();
+        demux.expect_confirmations("SUBSCRIBE", 2);
+        demux.expect_reply("PING");
+        assert_eq!(demux.pending(), 3);
+        let (frame, left) = parse_resp(input).unwrap();
+        assert!(matches!(
+            demux.route(frame).unwrap(),
+            Routed::Reply("SUBSCRIBE", Resp::Push(_))
+        ));
+        let (frame, left) = parse_resp(left).unwrap();
+        assert!(matches!(demux.route(frame).unwrap(), Routed::Push(_)));
+        let (frame, left) = parse_resp(left).unwrap();
+        assert!(matches!(
+            demux.route(frame).unwrap(),
+            Routed::Reply("SUBSCRIBE", Resp::Push(_))
+        ));
+        let (frame, _) = parse_resp(left).unwrap();
+        assert_eq!(
+            demux.route(frame).unwrap(),
+            Routed::Reply("PING", Resp::String(b"PONG"))
+        );
+
+        // Unexpected confirmations, like the ones of an UNSUBSCRIBE from everything
+        let input = b">3\r\n$11\r\nunsubscribe\r\n$1\r\na\r\n:0\r\n";
+        let (frame, _) = parse_resp(input).unwrap();
+        assert!(matches!(demux.route(frame).unwrap(), Routed::Push(_)));
+
+        demux.expect_confirmations("SSUBSCRIBE", 2);
+        demux.expect_reply("GET");
+        let input = b"-CROSSSLOT Keys in request don't hash to the same slot\r\n";
+        let (frame, _) = parse_resp(input).unwrap();
+        assert!(matches!(
+            demux.route(frame).unwrap(),
+            Routed::Reply("SSUBSCRIBE", Resp::Error(_))
+        ));
+        assert_eq!(demux.pending(), 1);
+        assert_eq!(
+            demux.route(Resp::NilBulk).unwrap(),
+            Routed::Reply("GET", Resp::NilBulk)
+        );
+    }
+
+    #[test]
+    pub fn test_route_pushes_to_channel() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut demux = Demultiplexer::new();
+        demux.expect_reply(1);
+        let input = b">2\r\n$10\r\ninvalidate\r\n*1\r\n$3\r\nfoo\r\n";
+        let (frame, _) = parse_resp(input).unwrap();
+        assert_eq!(demux.route_to(frame, &tx).unwrap(), None);
+        let reply = demux.route_to(Resp::NilBulk, &tx).unwrap();
+        assert_eq!(reply, Some((1, Resp::NilBulk)));
+        assert_eq!(
+            rx.try_recv().unwrap(),
+            OwnedResp::Push(vec![
+                OwnedResp::BulkString(b"invalidate".to_vec()),
+                OwnedResp::Array(vec![OwnedResp::BulkString(b"foo".to_vec())]),
+            ])
+        );
+        let mut pushes = Vec::new();
+        let reply = demux
+            .route_with(Resp::Push(vec![]), |push| pushes.push(push))
+            .unwrap();
+        assert_eq!(reply, None);
+        assert_eq!(pushes, vec![Resp::Push(vec![])]);
+    }
+}
@@ -0,0 +1,1066 @@
+//! Reading RDB files, whether saved on disk or received from a master during a full resync.
+//!
+//! [`RdbParser`] yields one event per record and, like the RESP parsers, returns
+//! [`RespError::NotEnoughBytes`] without consuming anything while a record is incomplete, so
+//! the file never needs to be in memory as a whole. Values are decoded into [`RdbValue`]
+//! whatever their encoding, integers stored as such are turned back into their decimal form.
+
+use crate::streams::StreamId;
+use crate::RespError;
+
+// Opcodes, see rdb.h
+const OPCODE_SLOT_INFO: u8 = 244;
+const OPCODE_FUNCTION2: u8 = 245;
+const OPCODE_FUNCTION_PRE_GA: u8 = 246;
+const OPCODE_MODULE_AUX: u8 = 247;
+const OPCODE_IDLE: u8 = 248;
+const OPCODE_FREQ: u8 = 249;
+const OPCODE_AUX: u8 = 250;
+const OPCODE_RESIZEDB: u8 = 251;
+const OPCODE_EXPIRETIME_MS: u8 = 252;
+const OPCODE_EXPIRETIME: u8 = 253;
+const OPCODE_SELECTDB: u8 = 254;
+const OPCODE_EOF: u8 = 255;
+
+// Value types
+pub(crate) const TYPE_STRING: u8 = 0;
+pub(crate) const TYPE_LIST: u8 = 1;
+pub(crate) const TYPE_SET: u8 = 2;
+const TYPE_ZSET: u8 = 3;
+pub(crate) const TYPE_HASH: u8 = 4;
+pub(crate) const TYPE_ZSET_2: u8 = 5;
+const TYPE_HASH_ZIPMAP: u8 = 9;
+const TYPE_LIST_ZIPLIST: u8 = 10;
+const TYPE_SET_INTSET: u8 = 11;
+const TYPE_ZSET_ZIPLIST: u8 = 12;
+const TYPE_HASH_ZIPLIST: u8 = 13;
+const TYPE_LIST_QUICKLIST: u8 = 14;
+pub(crate) const TYPE_STREAM_LISTPACKS: u8 = 15;
+const TYPE_HASH_LISTPACK: u8 = 16;
+const TYPE_ZSET_LISTPACK: u8 = 17;
+const TYPE_LIST_QUICKLIST_2: u8 = 18;
+pub(crate) const TYPE_STREAM_LISTPACKS_2: u8 = 19;
+const TYPE_SET_LISTPACK: u8 = 20;
+pub(crate) const TYPE_STREAM_LISTPACKS_3: u8 = 21;
+
+// Quicklist node containers
+const QUICKLIST_NODE_PLAIN: u64 = 1;
+
+// Flags of the entries in stream listpacks
+const STREAM_ITEM_FLAG_DELETED: i64 = 1;
+const STREAM_ITEM_FLAG_SAMEFIELDS: i64 = 2;
+
+/// Field and value pairs, of a hash or a stream entry.
+pub type Fields = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// A decoded value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RdbValue {
+    String(Vec<u8>),
+    List(Vec<Vec<u8>>),
+    Set(Vec<Vec<u8>>),
+    SortedSet(Vec<(Vec<u8>, f64)>),
+    Hash(Fields),
+    Stream(RdbStream),
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RdbStream {
+    /// The entries that were not deleted, in order.
+    pub entries: Vec<(StreamId, Fields)>,
+    pub length: u64,
+    pub last_id: StreamId,
+    /// Only stored since RDB 10 (Redis 7), like `max_deleted_id` and `entries_added`.
+    pub first_id: Option<StreamId>,
+    pub max_deleted_id: Option<StreamId>,
+    pub entries_added: Option<u64>,
+    pub groups: Vec<RdbConsumerGroup>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RdbConsumerGroup {
+    pub name: Vec<u8>,
+    pub last_id: StreamId,
+    pub entries_read: Option<u64>,
+    pub pending: Vec<RdbPendingEntry>,
+    pub consumers: Vec<RdbConsumer>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RdbPendingEntry {
+    pub id: StreamId,
+    /// Unix time in milliseconds.
+    pub delivery_time: u64,
+    pub delivery_count: u64,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RdbConsumer {
+    pub name: Vec<u8>,
+    /// Unix times in milliseconds, `active_time` is only stored since RDB 11.
+    pub seen_time: u64,
+    pub active_time: Option<u64>,
+    pub pending: Vec<StreamId>,
+}
+
+/// A record of an RDB file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RdbEvent {
+    Header {
+        version: u32,
+    },
+    /// Metadata about the server that wrote the file (`redis-ver`, `ctime`, `repl-id`...).
+    Aux {
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    /// The following entries belong to this database.
+    SelectDb(u64),
+    ResizeDb {
+        db_size: u64,
+        expires_size: u64,
+    },
+    /// A slot of a cluster node, with its size.
+    SlotInfo {
+        slot: u64,
+        size: u64,
+        expires_size: u64,
+    },
+    /// The code of a function library.
+    Function(Vec<u8>),
+    Entry {
+        key: Vec<u8>,
+        value: RdbValue,
+        /// Unix time in milliseconds.
+        expire_ms: Option<u64>,
+        /// LRU idle time in seconds, or LFU frequency, depending on the eviction policy.
+        idle: Option<u64>,
+        freq: Option<u8>,
+    },
+    /// The end of the file. The checksum has been verified, unless it was disabled when the
+    /// file was written, in which case it is `None`.
+    End {
+        checksum: Option<u64>,
+    },
+}
+
+/// CRC-64/Jones, as used for RDB files and `DUMP` payloads.
+pub fn crc64(crc: u64, input: &[u8]) -> u64 {
+    const POLY: u64 = 0x95ac_9329_ac4b_c9b5;
+    let mut crc = crc;
+    for &byte in input {
+        crc ^= byte as u64;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// Reads through an input, failing with [`RespError::NotEnoughBytes`] past its end.
+#[derive(Debug)]
+pub(crate) struct Cursor<'a> {
+    input: &'a [u8],
+    pub(crate) pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    pub(crate) fn new(input: &'a [u8]) -> Self {
+        Self { input, pos: 0 }
+    }
+
+    pub(crate) fn take(&mut self, n: u64) -> Result<&'a [u8], RespError> {
+        let n = usize::try_from(n).map_err(|_| RespError::IncorrectFormat)?;
+        if self.input.len() - self.pos < n {
+            return Err(RespError::NotEnoughBytes);
+        }
+        self.pos += n;
+        Ok(&self.input[self.pos - n..self.pos])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], RespError> {
+        Ok(self.take(N as u64)?.try_into().unwrap())
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, RespError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32_le(&mut self) -> Result<u32, RespError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub(crate) fn u64_le(&mut self) -> Result<u64, RespError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    // Returns the length and whether it is actually a special string encoding.
+    fn length(&mut self) -> Result<(u64, bool), RespError> {
+        let first = self.u8()?;
+        let length = match first >> 6 {
+            0 => (first & 0x3f) as u64,
+            1 => ((first & 0x3f) as u64) << 8 | self.u8()? as u64,
+            2 => match first {
+                0x80 => u32::from_be_bytes(self.array()?) as u64,
+                0x81 => u64::from_be_bytes(self.array()?),
+                _ => return Err(RespError::IncorrectFormat),
+            },
+            _ => return Ok(((first & 0x3f) as u64, true)),
+        };
+        Ok((length, false))
+    }
+
+    pub(crate) fn len(&mut self) -> Result<u64, RespError> {
+        match self.length()? {
+            (length, false) => Ok(length),
+            _ => Err(RespError::IncorrectFormat),
+        }
+    }
+
+    pub(crate) fn string(&mut self) -> Result<Vec<u8>, RespError> {
+        let string = match self.length()? {
+            (length, false) => self.take(length)?.to_vec(),
+            (0, true) => (self.u8()? as i8).to_string().into_bytes(),
+            (1, true) => i16::from_le_bytes(self.array()?).to_string().into_bytes(),
+            (2, true) => i32::from_le_bytes(self.array()?).to_string().into_bytes(),
+            (3, true) => {
+                let compressed_len = self.len()?;
+                let len = self.len()?;
+                lzf_decompress(self.take(compressed_len)?, len)?
+            }
+            _ => return Err(RespError::IncorrectFormat),
+        };
+        Ok(string)
+    }
+
+    fn strings(&mut self) -> Result<Vec<Vec<u8>>, RespError> {
+        (0..self.len()?).map(|_| self.string()).collect()
+    }
+
+    // A score of the original sorted set encoding, as a length prefixed string.
+    fn string_double(&mut self) -> Result<f64, RespError> {
+        match self.u8()? {
+            253 => Ok(f64::NAN),
+            254 => Ok(f64::INFINITY),
+            255 => Ok(f64::NEG_INFINITY),
+            len => Ok(std::str::from_utf8(self.take(len as u64)?)?.parse()?),
+        }
+    }
+
+    fn stream_id(&mut self) -> Result<StreamId, RespError> {
+        Ok(StreamId {
+            ms: self.len()?,
+            seq: self.len()?,
+        })
+    }
+
+    // An id stored as 128 bits big endian, like the keys of the stream radix tree.
+    fn raw_stream_id(&mut self) -> Result<StreamId, RespError> {
+        Ok(StreamId {
+            ms: u64::from_be_bytes(self.array()?),
+            seq: u64::from_be_bytes(self.array()?),
+        })
+    }
+}
+
+pub(crate) fn lzf_decompress(input: &[u8], len: u64) -> Result<Vec<u8>, RespError> {
+    let len = usize::try_from(len).map_err(|_| RespError::IncorrectFormat)?;
+    // A 3-byte back reference expands to at most 264 bytes, so a bogus length cannot make
+    // the output preallocate more than that ratio
+    let mut output = Vec::with_capacity(len.min(input.len().saturating_mul(88)));
+    let mut i = 0;
+    while i < input.len() {
+        let ctrl = input[i] as usize;
+        i += 1;
+        if ctrl < 32 {
+            let literal = input
+                .get(i..i + ctrl + 1)
+                .ok_or(RespError::IncorrectFormat)?;
+            if output.len() + literal.len() > len {
+                return Err(RespError::IncorrectFormat);
+            }
+            output.extend_from_slice(literal);
+            i += ctrl + 1;
+        } else {
+            let mut length = ctrl >> 5;
+            if length == 7 {
+                length += *input.get(i).ok_or(RespError::IncorrectFormat)? as usize;
+                i += 1;
+            }
+            let low = *input.get(i).ok_or(RespError::IncorrectFormat)? as usize;
+            i += 1;
+            let distance = ((ctrl & 0x1f) << 8) + low + 1;
+            let start = output
+                .len()
+                .checked_sub(distance)
+                .ok_or(RespError::IncorrectFormat)?;
+            if output.len() + length + 2 > len {
+                return Err(RespError::IncorrectFormat);
+            }
+            // The reference may overlap what it produces, so copy byte by byte
+            for j in start..start + length + 2 {
+                output.push(output[j]);
+            }
+        }
+    }
+    if output.len() != len {
+        return Err(RespError::IncorrectFormat);
+    }
+    Ok(output)
+}
+
+/// An element of a ziplist or listpack.
+#[derive(Debug)]
+enum Element<'a> {
+    Bytes(&'a [u8]),
+    Int(i64),
+}
+
+impl Element<'_> {
+    fn to_vec(&self) -> Vec<u8> {
+        match self {
+            Element::Bytes(b) => b.to_vec(),
+            Element::Int(i) => i.to_string().into_bytes(),
+        }
+    }
+
+    fn to_int(&self) -> Result<i64, RespError> {
+        match self {
+            Element::Bytes(b) => Ok(std::str::from_utf8(b)?.parse()?),
+            Element::Int(i) => Ok(*i),
+        }
+    }
+
+    fn to_double(&self) -> Result<f64, RespError> {
+        match self {
+            Element::Bytes(b) => Ok(std::str::from_utf8(b)?.parse()?),
+            Element::Int(i) => Ok(*i as f64),
+        }
+    }
+}
+
+// Parses a serialized structure found inside a string, where running out of bytes means the
+// structure itself is broken.
+pub(crate) fn nested<'a, T>(
+    blob: &'a [u8],
+    parse: impl FnOnce(&mut Cursor<'a>) -> Result<T, RespError>,
+) -> Result<T, RespError> {
+    parse(&mut Cursor::new(blob)).map_err(|err| match err {
+        RespError::NotEnoughBytes => RespError::IncorrectFormat,
+        err => err,
+    })
+}
+
+/// The size of the backwards length that follows a listpack entry of the given size.
+pub(crate) fn backlen_size(len: u64) -> u64 {
+    match len {
+        0..=127 => 1,
+        128..=16382 => 2,
+        16383..=2097150 => 3,
+        2097151..=268435454 => 4,
+        _ => 5,
+    }
+}
+
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+fn listpack(blob: &[u8]) -> Result<Vec<Element<'_>>, RespError> {
+    nested(blob, |c| {
+        // Total bytes and number of elements, which is not reliable past 65535
+        c.take(6)?;
+        let mut elements = Vec::new();
+        loop {
+            let first = c.u8()?;
+            let (element, len) = match first {
+                0xFF => break,
+                b if b & 0x80 == 0 => (Element::Int(b as i64), 1),
+                b if b & 0xC0 == 0x80 => {
+                    let len = (b & 0x3f) as u64;
+                    (Element::Bytes(c.take(len)?), 1 + len)
+                }
+                b if b & 0xE0 == 0xC0 => {
+                    let value = ((b & 0x1f) as u64) << 8 | c.u8()? as u64;
+                    (Element::Int(sign_extend(value, 13)), 2)
+                }
+                b if b & 0xF0 == 0xE0 => {
+                    let len = ((b & 0x0f) as u64) << 8 | c.u8()? as u64;
+                    (Element::Bytes(c.take(len)?), 2 + len)
+                }
+                0xF0 => {
+                    let len = c.u32_le()? as u64;
+                    (Element::Bytes(c.take(len)?), 5 + len)
+                }
+                0xF1 => (Element::Int(i16::from_le_bytes(c.array()?) as i64), 3),
+                0xF2 => {
+                    let [a, b, d] = c.array()?;
+                    let value = u32::from_le_bytes([a, b, d, 0]) as u64;
+                    (Element::Int(sign_extend(value, 24)), 4)
+                }
+                0xF3 => (Element::Int(i32::from_le_bytes(c.array()?) as i64), 5),
+                0xF4 => (Element::Int(i64::from_le_bytes(c.array()?)), 9),
+                _ => return Err(RespError::IncorrectFormat),
+            };
+            // Skip the length of the entry, stored backwards to walk the listpack from its end
+            c.take(backlen_size(len))?;
+            elements.push(element);
+        }
+        Ok(elements)
+    })
+}
+
+fn ziplist(blob: &[u8]) -> Result<Vec<Element<'_>>, RespError> {
+    nested(blob, |c| {
+        // Total bytes, offset of the last entry and number of entries
+        c.take(10)?;
+        let mut elements = Vec::new();
+        loop {
+            let prevlen = c.u8()?;
+            if prevlen == 0xFF {
+                break;
+            } else if prevlen == 254 {
+                c.take(4)?;
+            }
+            let encoding = c.u8()?;
+            let element = match encoding >> 6 {
+                0 => Element::Bytes(c.take((encoding & 0x3f) as u64)?),
+                1 => {
+                    let len = ((encoding & 0x3f) as u64) << 8 | c.u8()? as u64;
+                    Element::Bytes(c.take(len)?)
+                }
+                2 => {
+                    let len = u32::from_be_bytes(c.array()?) as u64;
+                    Element::Bytes(c.take(len)?)
+                }
+                _ => match encoding {
+                    0xC0 => Element::Int(i16::from_le_bytes(c.array()?) as i64),
+                    0xD0 => Element::Int(i32::from_le_bytes(c.array()?) as i64),
+                    0xE0 => Element::Int(i64::from_le_bytes(c.array()?)),
+                    0xF0 => {
+                        let [a, b, d] = c.array()?;
+                        let value = u32::from_le_bytes([a, b, d, 0]) as u64;
+                        Element::Int(sign_extend(value, 24))
+                    }
+                    0xFE => Element::Int(c.u8()? as i8 as i64),
+                    0xF1..=0xFD => Element::Int((encoding & 0x0f) as i64 - 1),
+                    _ => return Err(RespError::IncorrectFormat),
+                },
+            };
+            elements.push(element);
+        }
+        Ok(elements)
+    })
+}
+
+fn intset(blob: &[u8]) -> Result<Vec<Vec<u8>>, RespError> {
+    nested(blob, |c| {
+        let encoding = c.u32_le()?;
+        let len = c.u32_le()?;
+        (0..len)
+            .map(|_| {
+                let value = match encoding {
+                    2 => i16::from_le_bytes(c.array()?) as i64,
+                    4 => i32::from_le_bytes(c.array()?) as i64,
+                    8 => i64::from_le_bytes(c.array()?),
+                    _ => return Err(RespError::IncorrectFormat),
+                };
+                Ok(value.to_string().into_bytes())
+            })
+            .collect()
+    })
+}
+
+fn zipmap(blob: &[u8]) -> Result<Fields, RespError> {
+    nested(blob, |c| {
+        let zipmap_len = |c: &mut Cursor, first: u8| match first {
+            254 => Ok(c.u32_le()? as u64),
+            255 => Err(RespError::IncorrectFormat),
+            len => Ok(len as u64),
+        };
+        c.take(1)?;
+        let mut pairs = Vec::new();
+        loop {
+            let first = c.u8()?;
+            if first == 0xFF {
+                break;
+            }
+            let len = zipmap_len(c, first)?;
+            let field = c.take(len)?.to_vec();
+            let first = c.u8()?;
+            let len = zipmap_len(c, first)?;
+            let free = c.u8()?;
+            let value = c.take(len)?.to_vec();
+            c.take(free as u64)?;
+            pairs.push((field, value));
+        }
+        Ok(pairs)
+    })
+}
+
+fn to_vecs(elements: Vec<Element>) -> Vec<Vec<u8>> {
+    elements.iter().map(Element::to_vec).collect()
+}
+
+fn to_pairs(elements: Vec<Element>) -> Result<Fields, RespError> {
+    if elements.len() % 2 == 1 {
+        return Err(RespError::IncorrectFormat);
+    }
+    Ok(elements
+        .chunks(2)
+        .map(|pair| (pair[0].to_vec(), pair[1].to_vec()))
+        .collect())
+}
+
+fn to_scored(elements: Vec<Element>) -> Result<Vec<(Vec<u8>, f64)>, RespError> {
+    if elements.len() % 2 == 1 {
+        return Err(RespError::IncorrectFormat);
+    }
+    elements
+        .chunks(2)
+        .map(|pair| Ok((pair[0].to_vec(), pair[1].to_double()?)))
+        .collect()
+}
+
+// The entries of one listpack of a stream, relative to the master id of the listpack.
+fn stream_entries(master: StreamId, blob: &[u8]) -> Result<Vec<(StreamId, Fields)>, RespError> {
+    let elements = listpack(blob)?;
+    let mut elements = elements.iter();
+    let mut next = || elements.next().ok_or(RespError::IncorrectFormat);
+    let mut int = || -> Result<i64, RespError> { next()?.to_int() };
+    // Count of valid and deleted entries, then the fields of the master entry
+    int()?;
+    int()?;
+    let master_fields: Vec<Vec<u8>> = (0..int()?)
+        .map(|_| next().map(Element::to_vec))
+        .collect::<Result<_, _>>()?;
+    // The master entry ends with a zero where the other entries have their element count
+    if next()?.to_int()? != 0 {
+        return Err(RespError::IncorrectFormat);
+    }
+    let mut entries = Vec::new();
+    while let Some(flags) = elements.next() {
+        let mut next = || elements.next().ok_or(RespError::IncorrectFormat);
+        let flags = flags.to_int()?;
+        let id = StreamId {
+            ms: master.ms.wrapping_add(next()?.to_int()? as u64),
+            seq: master.seq.wrapping_add(next()?.to_int()? as u64),
+        };
+        let fields = if flags & STREAM_ITEM_FLAG_SAMEFIELDS != 0 {
+            master_fields
+                .iter()
+                .map(|field| Ok((field.clone(), next()?.to_vec())))
+                .collect::<Result<Vec<_>, RespError>>()?
+        } else {
+            (0..next()?.to_int()?)
+                .map(|_| Ok((next()?.to_vec(), next()?.to_vec())))
+                .collect::<Result<Vec<_>, RespError>>()?
+        };
+        // Number of elements of the entry, used to walk the listpack backwards
+        next()?;
+        if flags & STREAM_ITEM_FLAG_DELETED == 0 {
+            entries.push((id, fields));
+        }
+    }
+    Ok(entries)
+}
+
+fn stream(c: &mut Cursor, kind: u8) -> Result<RdbStream, RespError> {
+    let mut stream = RdbStream::default();
+    for _ in 0..c.len()? {
+        let master = c.string()?;
+        let master = nested(&master, Cursor::raw_stream_id)?;
+        let blob = c.string()?;
+        stream.entries.extend(stream_entries(master, &blob)?);
+    }
+    stream.length = c.len()?;
+    stream.last_id = c.stream_id()?;
+    if kind >= TYPE_STREAM_LISTPACKS_2 {
+        stream.first_id = Some(c.stream_id()?);
+        stream.max_deleted_id = Some(c.stream_id()?);
+        stream.entries_added = Some(c.len()?);
+    }
+    for _ in 0..c.len()? {
+        let mut group = RdbConsumerGroup {
+            name: c.string()?,
+            last_id: c.stream_id()?,
+            ..RdbConsumerGroup::default()
+        };
+        if kind >= TYPE_STREAM_LISTPACKS_2 {
+            group.entries_read = Some(c.len()?);
+        }
+        for _ in 0..c.len()? {
+            group.pending.push(RdbPendingEntry {
+                id: c.raw_stream_id()?,
+                delivery_time: c.u64_le()?,
+                delivery_count: c.len()?,
+            });
+        }
+        for _ in 0..c.len()? {
+            let mut consumer = RdbConsumer {
+                name: c.string()?,
+                seen_time: c.u64_le()?,
+                ..RdbConsumer::default()
+            };
+            if kind >= TYPE_STREAM_LISTPACKS_3 {
+                consumer.active_time = Some(c.u64_le()?);
+            }
+            for _ in 0..c.len()? {
+                consumer.pending.push(c.raw_stream_id()?);
+            }
+            group.consumers.push(consumer);
+        }
+        stream.groups.push(group);
+    }
+    Ok(stream)
+}
+
+/// Reads a value of the given type, as found after the key of an entry or in a `DUMP`
+/// payload.
+pub(crate) fn read_value(c: &mut Cursor, kind: u8) -> Result<RdbValue, RespError> {
+    let value = match kind {
+        TYPE_STRING => RdbValue::String(c.string()?),
+        TYPE_LIST => RdbValue::List(c.strings()?),
+        TYPE_SET => RdbValue::Set(c.strings()?),
+        TYPE_ZSET | TYPE_ZSET_2 => {
+            let members = (0..c.len()?)
+                .map(|_| {
+                    let member = c.string()?;
+                    let score = match kind {
+                        TYPE_ZSET => c.string_double()?,
+                        _ => f64::from_le_bytes(c.array()?),
+                    };
+                    Ok((member, score))
+                })
+                .collect::<Result<_, RespError>>()?;
+            RdbValue::SortedSet(members)
+        }
+        TYPE_HASH => {
+            let pairs = (0..c.len()?)
+                .map(|_| Ok((c.string()?, c.string()?)))
+                .collect::<Result<_, RespError>>()?;
+            RdbValue::Hash(pairs)
+        }
+        TYPE_HASH_ZIPMAP => RdbValue::Hash(zipmap(&c.string()?)?),
+        TYPE_LIST_ZIPLIST => RdbValue::List(to_vecs(ziplist(&c.string()?)?)),
+        TYPE_SET_INTSET => RdbValue::Set(intset(&c.string()?)?),
+        TYPE_ZSET_ZIPLIST => RdbValue::SortedSet(to_scored(ziplist(&c.string()?)?)?),
+        TYPE_HASH_ZIPLIST => RdbValue::Hash(to_pairs(ziplist(&c.string()?)?)?),
+        TYPE_LIST_QUICKLIST => {
+            let mut list = Vec::new();
+            for _ in 0..c.len()? {
+                list.extend(to_vecs(ziplist(&c.string()?)?));
+            }
+            RdbValue::List(list)
+        }
+        TYPE_LIST_QUICKLIST_2 => {
+            let mut list = Vec::new();
+            for _ in 0..c.len()? {
+                let container = c.len()?;
+                let node = c.string()?;
+                if container == QUICKLIST_NODE_PLAIN {
+                    list.push(node);
+                } else {
+                    list.extend(to_vecs(listpack(&node)?));
+                }
+            }
+            RdbValue::List(list)
+        }
+        TYPE_HASH_LISTPACK => RdbValue::Hash(to_pairs(listpack(&c.string()?)?)?),
+        TYPE_ZSET_LISTPACK => RdbValue::SortedSet(to_scored(listpack(&c.string()?)?)?),
+        TYPE_SET_LISTPACK => RdbValue::Set(to_vecs(listpack(&c.string()?)?)),
+        TYPE_STREAM_LISTPACKS | TYPE_STREAM_LISTPACKS_2 | TYPE_STREAM_LISTPACKS_3 => {
+            RdbValue::Stream(stream(c, kind)?)
+        }
+        kind => {
+            return Err(RespError::Other(
+                format!("unsupported RDB value type {}", kind).into(),
+            ))
+        }
+    };
+    Ok(value)
+}
+
+/// Parses an RDB file record by record.
+#[derive(Debug, Default)]
+pub struct RdbParser {
+    version: Option<u32>,
+    crc: u64,
+    done: bool,
+}
+
+impl RdbParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The version from the header, once it has been read.
+    pub fn version(&self) -> Option<u32> {
+        self.version
+    }
+
+    pub fn next_event<'a>(&mut self, input: &'a [u8]) -> Result<(RdbEvent, &'a [u8]), RespError> {
+        if self.done {
+            return Err(RespError::IncorrectFormat);
+        }
+        let mut c = Cursor::new(input);
+        let event = match self.version {
+            None => {
+                let magic = c.take(9)?;
+                if &magic[..5] != b"REDIS" {
+                    return Err(RespError::IncorrectFormat);
+                }
+                let version = std::str::from_utf8(&magic[5..])?.parse()?;
+                RdbEvent::Header { version }
+            }
+            Some(version) => self.record(&mut c, version)?,
+        };
+        match event {
+            RdbEvent::Header { version } => self.version = Some(version),
+            RdbEvent::End { .. } => self.done = true,
+            _ => {}
+        }
+        self.crc = crc64(self.crc, &input[..c.pos]);
+        Ok((event, &input[c.pos..]))
+    }
+
+    fn record(&self, c: &mut Cursor, version: u32) -> Result<RdbEvent, RespError> {
+        let (mut expire_ms, mut idle, mut freq) = (None, None, None);
+        loop {
+            let event = match c.u8()? {
+                OPCODE_EOF => {
+                    // The checksum was only added in version 5
+                    if version < 5 {
+                        return Ok(RdbEvent::End { checksum: None });
+                    }
+                    let crc = crc64(self.crc, &c.input[..c.pos]);
+                    let checksum = c.u64_le()?;
+                    if checksum == 0 {
+                        return Ok(RdbEvent::End { checksum: None });
+                    } else if checksum != crc {
+                        return Err(RespError::Other(
+                            format!("RDB checksum mismatch: {:x} != {:x}", checksum, crc).into(),
+                        ));
+                    }
+                    RdbEvent::End {
+                        checksum: Some(checksum),
+                    }
+                }
+                OPCODE_SELECTDB => RdbEvent::SelectDb(c.len()?),
+                OPCODE_EXPIRETIME => {
+                    expire_ms = Some(c.u32_le()? as u64 * 1000);
+                    continue;
+                }
+                OPCODE_EXPIRETIME_MS => {
+                    expire_ms = Some(c.u64_le()?);
+                    continue;
+                }
+                OPCODE_IDLE => {
+                    idle = Some(c.len()?);
+                    continue;
+                }
+                OPCODE_FREQ => {
+                    freq = Some(c.u8()?);
+                    continue;
+                }
+                OPCODE_RESIZEDB => RdbEvent::ResizeDb {
+                    db_size: c.len()?,
+                    expires_size: c.len()?,
+                },
+                OPCODE_AUX => RdbEvent::Aux {
+                    key: c.string()?,
+                    value: c.string()?,
+                },
+                OPCODE_SLOT_INFO => RdbEvent::SlotInfo {
+                    slot: c.len()?,
+                    size: c.len()?,
+                    expires_size: c.len()?,
+                },
+                OPCODE_FUNCTION2 => RdbEvent::Function(c.string()?),
+                opcode @ (OPCODE_FUNCTION_PRE_GA | OPCODE_MODULE_AUX) => {
+                    return Err(RespError::Other(
+                        format!("unsupported RDB opcode {}", opcode).into(),
+                    ))
+                }
+                kind => RdbEvent::Entry {
+                    key: c.string()?,
+                    value: read_value(c, kind)?,
+                    expire_ms,
+                    idle,
+                    freq,
+                },
+            };
+            return Ok(event);
+        }
+    }
+}
+
+/// Parses a whole RDB file, up to and including its checksum.
+pub fn parse_rdb(input: &[u8]) -> Result<Vec<RdbEvent>, RespError> {
+    let mut parser = RdbParser::new();
+    let mut events = Vec::new();
+    let mut input = input;
+    loop {
+        let (event, leftover) = parser.next_event(input)?;
+        input = leftover;
+        let end = matches!(event, RdbEvent::End { .. });
+        events.push(event);
+        if end {
+            return Ok(events);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn value(kind: u8, input: &[u8]) -> RdbValue {
+        let mut c = Cursor::new(input);
+        let value = read_value(&mut c, kind).unwrap();
+        assert_eq!(c.pos, input.len());
+        value
+    }
+
+    // A string with a 6 bit length.
+    fn string(s: &[u8]) -> Vec<u8> {
+        [&[s.len() as u8][..], s].concat()
+    }
+
+    // A listpack of small strings and integers.
+    fn listpack_of(elements: &[&[u8]]) -> Vec<u8> {
+        let mut output = vec![0; 6];
+        for e in elements {
+            match std::str::from_utf8(e).unwrap().parse::<u8>() {
+                Ok(i) if i < 128 => output.extend_from_slice(&[i, 1]),
+                _ => {
+                    output.push(0x80 | e.len() as u8);
+                    output.extend_from_slice(e);
+                    output.push(1 + e.len() as u8);
+                }
+            }
+        }
+        output.push(0xFF);
+        output
+    }
+
+    fn strings(s: &[&str]) -> Vec<Vec<u8>> {
+        s.iter().map(|s| s.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    pub fn test_crc64() {
+        assert_eq!(crc64(0, b"123456789"), 0xe9c6d914c4b8d9ca);
+    }
+
+    #[test]
+    pub fn test_strings() {
+        assert_eq!(value(0, b"\x03foo"), RdbValue::String(b"foo".to_vec()));
+        assert_eq!(value(0, b"\xC0\x85"), RdbValue::String(b"-123".to_vec()));
+        assert_eq!(
+            value(0, b"\xC1\x39\x30"),
+            RdbValue::String(b"12345".to_vec())
+        );
+        assert_eq!(
+            value(0, b"\xC2\x87\xD6\x12\x00"),
+            RdbValue::String(b"1234567".to_vec())
+        );
+        // "abc" as a literal followed by a back reference of 6 bytes
+        assert_eq!(
+            value(0, b"\xC3\x06\x09\x02abc\x80\x02"),
+            RdbValue::String(b"abcabcabc".to_vec())
+        );
+        assert!(lzf_decompress(b"\x80\x02", 6).is_err());
+        // Longer or shorter than announced
+        assert!(lzf_decompress(b"\x02abc\x80\x02", 8).is_err());
+        assert!(lzf_decompress(b"\x02abc\x80\x02", 10).is_err());
+        assert!(lzf_decompress(b"\x02abc", u32::MAX as u64).is_err());
+        let mut c = Cursor::new(b"\x4F\xFF");
+        assert_eq!(c.len().unwrap(), 0x0FFF);
+        let mut c = Cursor::new(b"\x80\x00\x01\x00\x00");
+        assert_eq!(c.len().unwrap(), 0x10000);
+    }
+
+    #[test]
+    pub fn test_encodings() {
+        let set = [&b"\x02"[..], &b"\x01a"[..], &b"\x01b"[..]].concat();
+        assert_eq!(value(2, &set), RdbValue::Set(strings(&["a", "b"])));
+        assert_eq!(
+            value(5, &[&b"\x01\x01a"[..], &1.5f64.to_le_bytes()].concat()),
+            RdbValue::SortedSet(vec![(b"a".to_vec(), 1.5)])
+        );
+        assert_eq!(
+            value(3, b"\x01\x01a\x03inf"),
+            RdbValue::SortedSet(vec![(b"a".to_vec(), f64::INFINITY)])
+        );
+
+        let intset = string(b"\x02\x00\x00\x00\x02\x00\x00\x00\x01\x00\xFF\xFF");
+        assert_eq!(value(11, &intset), RdbValue::Set(strings(&["1", "-1"])));
+
+        let lp = string(&listpack_of(&[b"f", b"v", b"n", b"7"]));
+        assert_eq!(
+            value(16, &lp),
+            RdbValue::Hash(vec![
+                (b"f".to_vec(), b"v".to_vec()),
+                (b"n".to_vec(), b"7".to_vec())
+            ])
+        );
+        let lp = string(&listpack_of(&[b"a", b"1", b"b", b"2.5"]));
+        assert_eq!(
+            value(17, &lp),
+            RdbValue::SortedSet(vec![(b"a".to_vec(), 1.0), (b"b".to_vec(), 2.5)])
+        );
+        // A packed node then a plain one
+        let quicklist = [
+            &b"\x02\x02"[..],
+            &string(&listpack_of(&[b"a", b"b"])),
+            b"\x01",
+            &string(b"big"),
+        ]
+        .concat();
+        assert_eq!(
+            value(18, &quicklist),
+            RdbValue::List(strings(&["a", "b", "big"]))
+        );
+
+        // "ab", 5 as an immediate and -2 as an 8 bit integer
+        let zl =
+            string(b"\x0F\x00\x00\x00\x00\x00\x00\x00\x03\x00\x00\x02ab\x04\xF6\x02\xFE\xFE\xFF");
+        assert_eq!(value(10, &zl), RdbValue::List(strings(&["ab", "5", "-2"])));
+        let zipmap = string(b"\x01\x01f\x01\x00v\xFF");
+        assert_eq!(
+            value(9, &zipmap),
+            RdbValue::Hash(vec![(b"f".to_vec(), b"v".to_vec())])
+        );
+        assert!(matches!(
+            read_value(&mut Cursor::new(&string(b"\x00")), 16),
+            Err(RespError::IncorrectFormat)
+        ));
+    }
+
+    #[test]
+    pub fn test_stream() {
+        // Master id 1-0 with fields [f], an entry 1-0 with the same fields, then an entry
+        // 1-1 with its own fields, then a deleted one
+        let lp = listpack_of(&[
+            b"3", b"1", b"1", b"f", b"0", //
+            b"2", b"0", b"0", b"a", b"4", //
+            b"0", b"0", b"1", b"1", b"g", b"b", b"6", //
+            b"3", b"0", b"2", b"c", b"4",
+        ]);
+        let mut master = Vec::new();
+        master.extend_from_slice(&1u64.to_be_bytes());
+        master.extend_from_slice(&0u64.to_be_bytes());
+        let mut input = vec![1];
+        input.extend(string(&master));
+        input.extend(string(&lp));
+        // length, last id, first id, max deleted id, entries added
+        input.extend_from_slice(b"\x02\x01\x02\x01\x00\x01\x02\x03");
+        // One group with one pending entry, owned by its only consumer
+        input.extend_from_slice(b"\x01\x02g1\x01\x01\x02\x01");
+        let mut pending = master.clone();
+        pending.extend_from_slice(&1000u64.to_le_bytes());
+        pending.push(3);
+        input.extend(pending);
+        input.extend_from_slice(b"\x01\x02c1");
+        input.extend_from_slice(&2000u64.to_le_bytes());
+        input.extend_from_slice(&3000u64.to_le_bytes());
+        input.push(1);
+        input.extend(&master);
+
+        let stream = match value(TYPE_STREAM_LISTPACKS_3, &input) {
+            RdbValue::Stream(stream) => stream,
+            value => panic!("{:?}", value),
+        };
+        let id = |ms, seq| StreamId { ms, seq };
+        assert_eq!(
+            stream.entries,
+            vec![
+                (id(1, 0), vec![(b"f".to_vec(), b"a".to_vec())]),
+                (id(1, 1), vec![(b"g".to_vec(), b"b".to_vec())]),
+            ]
+        );
+        assert_eq!(stream.last_id, id(1, 2));
+        assert_eq!(stream.entries_added, Some(3));
+        let group = &stream.groups[0];
+        assert_eq!(group.name, b"g1".to_vec());
+        assert_eq!(group.last_id, id(1, 1));
+        assert_eq!(group.entries_read, Some(2));
+        assert_eq!(group.pending[0].delivery_count, 3);
+        assert_eq!(group.consumers[0].active_time, Some(3000));
+        assert_eq!(group.consumers[0].pending, vec![id(1, 0)]);
+    }
+
+    #[test]
+    pub fn test_file() {
+        let mut input = b"REDIS0011".to_vec();
+        input.extend_from_slice(b"\xFA\x09redis-ver\x057.2.4");
+        input.extend_from_slice(b"\xFE\x00\xFB\x02\x01");
+        input.extend_from_slice(b"\xFC");
+        input.extend_from_slice(&1700000000000u64.to_le_bytes());
+        input.extend_from_slice(b"\x00\x01k\xC0\x7B");
+        input.extend_from_slice(b"\xF8\x0A\x02\x01s\x01\x01m");
+        input.push(0xFF);
+        let crc = crc64(0, &input);
+        input.extend_from_slice(&crc.to_le_bytes());
+
+        let events = parse_rdb(&input).unwrap();
+        assert_eq!(
+            events,
+            vec![
+                RdbEvent::Header { version: 11 },
+                RdbEvent::Aux {
+                    key: b"redis-ver".to_vec(),
+                    value: b"7.2.4".to_vec()
+                },
+                RdbEvent::SelectDb(0),
+                RdbEvent::ResizeDb {
+                    db_size: 2,
+                    expires_size: 1
+                },
+                RdbEvent::Entry {
+                    key: b"k".to_vec(),
+                    value: RdbValue::String(b"123".to_vec()),
+                    expire_ms: Some(1700000000000),
+                    idle: None,
+                    freq: None
+                },
+                RdbEvent::Entry {
+                    key: b"s".to_vec(),
+                    value: RdbValue::Set(strings(&["m"])),
+                    expire_ms: None,
+                    idle: Some(10),
+                    freq: None
+                },
+                RdbEvent::End {
+                    checksum: Some(crc)
+                },
+            ]
+        );
+
+        // Fed byte by byte, an incomplete record is never consumed
+        let mut parser = RdbParser::new();
+        let (mut end, mut count) = (1, 0);
+        let mut start = 0;
+        while count < events.len() {
+            match parser.next_event(&input[start..end]) {
+                Ok((_, leftover)) => {
+                    start = end - leftover.len();
+                    count += 1;
+                }
+                Err(RespError::NotEnoughBytes) => end += 1,
+                Err(err) => panic!("{:?}", err),
+            }
+        }
+        assert_eq!(start, input.len());
+
+        let last = input.len() - 1;
+        input[last] ^= 1;
+        assert!(matches!(parse_rdb(&input), Err(RespError::Other(_))));
+    }
+}
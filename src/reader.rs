@@ -0,0 +1,262 @@
+use crate::{parse_resp, OwnedResp, RespError};
+use std::io::{ErrorKind, Read};
+
+// Smallest amount of room left in the buffer for each read
+pub(crate) const READ_CHUNK: usize = 4096;
+
+/// Parses the frame at the front of `buffered`, along with the number of bytes it takes.
+///
+/// Returns `None` while `buffered` only holds part of a frame.
+fn parse_frame(buffered: &[u8]) -> Result<Option<(OwnedResp, usize)>, RespError> {
+    match parse_resp(buffered) {
+        Ok((resp, leftover)) => {
+            let len = buffered.len() - leftover.len();
+            Ok(Some((OwnedResp::from(resp), len)))
+        }
+        Err(RespError::NotEnoughBytes) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// What reaching the end of the stream with `buffered` bytes left means: the stream ended
+/// cleanly if it ended between two frames.
+pub(crate) fn end_of_stream(buffered: &[u8]) -> Result<Option<OwnedResp>, RespError> {
+    if buffered.is_empty() {
+        Ok(None)
+    } else {
+        Err(std::io::Error::from(ErrorKind::UnexpectedEof).into())
+    }
+}
+
+/// Bytes read ahead of what has been parsed.
+///
+/// Kept by [`RespReader`], and passed to every call of the `read_frame` functions of
+/// [`crate::async_io`] for a given stream.
+#[derive(Debug, Default)]
+pub struct ReadBuffer {
+    // Zeroed once and reused, only `start..filled` holds bytes to parse
+    buffer: Vec<u8>,
+    // Start of the bytes that have not been consumed yet
+    start: usize,
+    // End of the bytes read so far, the rest is room for the next reads
+    filled: usize,
+}
+
+impl ReadBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bytes that have been read but not parsed yet.
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer[self.start..self.filled]
+    }
+
+    pub(crate) fn consume(&mut self, len: usize) {
+        self.start += len;
+    }
+
+    // Parses the frame at the front of the buffered bytes and consumes them.
+    pub(crate) fn take_frame(&mut self) -> Result<Option<OwnedResp>, RespError> {
+        match parse_frame(self.buffered())? {
+            Some((frame, len)) => {
+                self.consume(len);
+                Ok(Some(frame))
+            }
+            None => Ok(None),
+        }
+    }
+
+    // Drops the consumed bytes and returns the room for the next read, which only counts as
+    // buffered once passed to `advance`.
+    //
+    // The buffer doubles whenever it runs out of room, so zeroing it stays linear in the size
+    // of the largest frame. A frame is still parsed again from its start after every read,
+    // which is cheap for bulk strings since their length comes first, but not for large
+    // aggregates arriving in small reads.
+    pub(crate) fn room(&mut self) -> &mut [u8] {
+        if self.start > 0 {
+            self.buffer.copy_within(self.start..self.filled, 0);
+            self.filled -= self.start;
+            self.start = 0;
+        }
+        if self.buffer.len() - self.filled < READ_CHUNK {
+            let len = (self.buffer.len() * 2).max(self.filled + READ_CHUNK);
+            self.buffer.resize(len, 0);
+        }
+        &mut self.buffer[self.filled..]
+    }
+
+    // Marks `n` bytes read into `room` as buffered.
+    pub(crate) fn advance(&mut self, n: usize) {
+        self.filled += n;
+    }
+
+    // Reads more, returning false at the end of the stream.
+    pub(crate) fn fill<R: Read>(&mut self, reader: &mut R) -> Result<bool, RespError> {
+        let n = loop {
+            match reader.read(self.room()) {
+                Ok(n) => break n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            }
+        };
+        self.advance(n);
+        Ok(n > 0)
+    }
+}
+
+/// Reads frames from a blocking [`Read`], the reading counterpart of
+/// [`crate::Resp::write_to_writer`].
+///
+/// Bytes read past the end of a frame are kept for the next one, so a single reader must be
+/// used for the whole lifetime of the stream. Frames can be read one at a time with
+/// [`RespReader::read_frame`] or by iterating over the reader, in which case iteration stops
+/// after the first error.
+#[derive(Debug)]
+pub struct RespReader<R> {
+    reader: R,
+    buffer: ReadBuffer,
+    failed: bool,
+}
+
+impl<R: Read> RespReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: ReadBuffer::default(),
+            failed: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Bytes that have been read from the underlying reader but not parsed yet.
+    pub fn buffered(&self) -> &[u8] {
+        self.buffer.buffered()
+    }
+
+    /// Returns the underlying reader, losing whatever is still buffered.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Parses the next frame if it has been read in full already, without reading from the
+    /// underlying reader.
+    pub fn read_buffered_frame(&mut self) -> Result<Option<OwnedResp>, RespError> {
+        self.buffer.take_frame()
+    }
+
+    /// Reads the next frame, or `None` if the stream ends cleanly between two frames.
+    pub fn read_frame(&mut self) -> Result<Option<OwnedResp>, RespError> {
+        loop {
+            if let Some(frame) = self.buffer.take_frame()? {
+                return Ok(Some(frame));
+            }
+            if !self.buffer.fill(&mut self.reader)? {
+                return end_of_stream(self.buffered());
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for RespReader<R> {
+    type Item = Result<OwnedResp, RespError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let frame = self.read_frame().transpose();
+        self.failed = matches!(frame, Some(Err(_)));
+        frame
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    /// Hands out its input a few bytes at a time.
+    pub(crate) struct Trickle<'a> {
+        pub input: &'a [u8],
+        pub step: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.step.min(buf.len()).min(self.input.len());
+            buf[..n].copy_from_slice(&self.input[..n]);
+            self.input = &self.input[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    pub fn test_read_frames() {
+        let input = b"+OK\r\n*2\r\n$3\r\nfoo\r\n$-1\r\n:10\r\n";
+        for step in [1, 3, 1024] {
+            let reader = RespReader::new(Trickle { input, step });
+            let frames = reader.collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(
+                frames,
+                vec![
+                    OwnedResp::String(b"OK".to_vec()),
+                    OwnedResp::Array(vec![
+                        OwnedResp::BulkString(b"foo".to_vec()),
+                        OwnedResp::NilBulk
+                    ]),
+                    OwnedResp::Integer(b"10".to_vec()),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_large_frame() {
+        let payload = vec![b'x'; 100_000];
+        let mut input = format!("${}\r\n", payload.len()).into_bytes();
+        input.extend_from_slice(&payload);
+        input.extend_from_slice(b"\r\n+next\r\n");
+        let mut reader = RespReader::new(Trickle {
+            input: &input,
+            step: 5000,
+        });
+        let frame = reader.read_frame().unwrap();
+        assert_eq!(frame, Some(OwnedResp::BulkString(payload)));
+        assert!(reader.buffered().len() <= b"+next\r\n".len());
+        let frame = reader.read_frame().unwrap();
+        assert_eq!(frame, Some(OwnedResp::String(b"next".to_vec())));
+        assert_eq!(reader.read_frame().unwrap(), None);
+    }
+
+    #[test]
+    pub fn test_read_buffered_frame() {
+        let mut reader = RespReader::new(&b"+OK\r\n:1\r\n$3\r\nfo"[..]);
+        assert_eq!(reader.read_buffered_frame().unwrap(), None);
+        let frame = reader.read_frame().unwrap();
+        assert_eq!(frame, Some(OwnedResp::String(b"OK".to_vec())));
+        let frame = reader.read_buffered_frame().unwrap();
+        assert_eq!(frame, Some(OwnedResp::Integer(b"1".to_vec())));
+        assert_eq!(reader.read_buffered_frame().unwrap(), None);
+        assert_eq!(reader.buffered(), b"$3\r\nfo");
+    }
+
+    #[test]
+    pub fn test_read_errors() {
+        let mut reader = RespReader::new(&b"+OK\r\n$5\r\nfoo"[..]);
+        assert!(reader.next().unwrap().is_ok());
+        let err = reader.next().unwrap().unwrap_err();
+        assert!(matches!(err, RespError::Other(_)));
+        assert!(reader.next().is_none());
+        let mut reader = RespReader::new(&b"$3\r\nfoobar\r\n"[..]);
+        let err = reader.read_frame().unwrap_err();
+        assert!(matches!(err, RespError::IncorrectFormat));
+    }
+}
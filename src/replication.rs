@@ -0,0 +1,278 @@
+use crate::{parse_everything_until_crlf, parse_resp, Resp, RespError};
+
+// Length of the random delimiter of a diskless RDB transfer
+const EOF_MARK_LEN: usize = 40;
+
+/// Events yielded by [`ReplicationParser`] while following a master as a replica.
+#[derive(Debug, Eq, PartialEq)]
+pub enum ReplicationEvent<'a> {
+    /// Newlines sent by the master to keep the connection alive while it prepares the RDB.
+    KeepAlive,
+    /// `+FULLRESYNC`, an RDB payload follows.
+    FullResync { replid: &'a [u8], offset: i64 },
+    /// `+CONTINUE`, the command stream resumes where the replica left off. The replication id
+    /// is only sent when it changed.
+    Continue { replid: Option<&'a [u8]> },
+    /// The header of the RDB payload, with its length unless it is sent diskless, in which
+    /// case it ends with a delimiter instead.
+    RdbStart { len: Option<usize> },
+    /// Part of the RDB payload.
+    RdbChunk(&'a [u8]),
+    /// The end of the RDB payload, the command stream follows.
+    RdbEnd,
+    /// A command propagated by the master, `PING` and `REPLCONF GETACK` included.
+    Command(Resp<'a>),
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum State {
+    Handshake,
+    RdbHeader,
+    RdbLength { remaining: usize },
+    RdbEof { mark: [u8; EOF_MARK_LEN] },
+    Commands,
+}
+
+/// Parses what a master sends to a replica after `PSYNC` or `SYNC`.
+///
+/// The RDB payload is not framed like a bulk string: its `$<len>\r\n` header is not followed
+/// by a CRLF after the payload, and diskless transfers send `$EOF:<mark>\r\n` then end the
+/// payload with the same 40 bytes mark. The payload is yielded in chunks, so it never needs to
+/// be in memory as a whole.
+///
+/// Like [`crate::chunked::BulkDecoder`], each call consumes what it can and returns the
+/// leftover, and [`RespError::NotEnoughBytes`] means more input is needed.
+#[derive(Debug)]
+pub struct ReplicationParser {
+    state: State,
+    offset: i64,
+}
+
+impl Default for ReplicationParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn keep_alive(input: &[u8]) -> Option<&[u8]> {
+    let newlines = input.iter().take_while(|&&c| c == b'\n').count();
+    if newlines > 0 {
+        Some(&input[newlines..])
+    } else {
+        None
+    }
+}
+
+impl ReplicationParser {
+    /// A parser expecting the reply to `PSYNC`.
+    pub fn new() -> Self {
+        Self {
+            state: State::Handshake,
+            offset: 0,
+        }
+    }
+
+    /// A parser for the old `SYNC` command, where the master sends the RDB payload right
+    /// away.
+    pub fn after_sync() -> Self {
+        Self {
+            state: State::RdbHeader,
+            offset: 0,
+        }
+    }
+
+    /// The replication offset of the data seen so far, to acknowledge with `REPLCONF ACK`.
+    pub fn offset(&self) -> i64 {
+        self.offset
+    }
+
+    /// Sets the offset the stream resumes from, before a `+CONTINUE`.
+    pub fn set_offset(&mut self, offset: i64) {
+        self.offset = offset;
+    }
+
+    pub fn next_event<'a>(
+        &mut self,
+        input: &'a [u8],
+    ) -> Result<(ReplicationEvent<'a>, &'a [u8]), RespError> {
+        match self.state {
+            State::Handshake | State::RdbHeader if input.is_empty() => {
+                Err(RespError::NotEnoughBytes)
+            }
+            State::Handshake | State::RdbHeader if keep_alive(input).is_some() => {
+                Ok((ReplicationEvent::KeepAlive, keep_alive(input).unwrap()))
+            }
+            State::Handshake => self.handshake(input),
+            State::RdbHeader => {
+                if input[0] != b'$' {
+                    return Err(RespError::IncorrectFormat);
+                }
+                let (header, leftover) = parse_everything_until_crlf(&input[1..])?;
+                if let Some(mark) = header.strip_prefix(b"EOF:") {
+                    let mark = mark.try_into().map_err(|_| RespError::IncorrectFormat)?;
+                    self.state = State::RdbEof { mark };
+                    return Ok((ReplicationEvent::RdbStart { len: None }, leftover));
+                }
+                let len = std::str::from_utf8(header)?.parse::<usize>()?;
+                self.state = State::RdbLength { remaining: len };
+                Ok((ReplicationEvent::RdbStart { len: Some(len) }, leftover))
+            }
+            State::RdbLength { remaining: 0 } => {
+                self.state = State::Commands;
+                Ok((ReplicationEvent::RdbEnd, input))
+            }
+            State::RdbLength { remaining } => {
+                if input.is_empty() {
+                    return Err(RespError::NotEnoughBytes);
+                }
+                let taken = remaining.min(input.len());
+                self.state = State::RdbLength {
+                    remaining: remaining - taken,
+                };
+                Ok((ReplicationEvent::RdbChunk(&input[..taken]), &input[taken..]))
+            }
+            State::RdbEof { mark } => {
+                if input.starts_with(&mark) {
+                    self.state = State::Commands;
+                    return Ok((ReplicationEvent::RdbEnd, &input[EOF_MARK_LEN..]));
+                }
+                let found = input.windows(EOF_MARK_LEN).position(|w| w == mark);
+                // Without the mark, the tail may still be the start of it
+                let taken = match found {
+                    Some(index) => index,
+                    None if input.len() >= EOF_MARK_LEN => input.len() - (EOF_MARK_LEN - 1),
+                    None => return Err(RespError::NotEnoughBytes),
+                };
+                Ok((ReplicationEvent::RdbChunk(&input[..taken]), &input[taken..]))
+            }
+            State::Commands => {
+                let (command, leftover) = parse_resp(input)?;
+                self.offset += (input.len() - leftover.len()) as i64;
+                Ok((ReplicationEvent::Command(command), leftover))
+            }
+        }
+    }
+
+    fn handshake<'a>(
+        &mut self,
+        input: &'a [u8],
+    ) -> Result<(ReplicationEvent<'a>, &'a [u8]), RespError> {
+        let (reply, leftover) = parse_resp(input)?;
+        let line = match reply {
+            Resp::String(line) => line,
+            Resp::Error(e) => return Err(RespError::Other(String::from_utf8_lossy(e).into())),
+            _ => return Err(RespError::IncorrectFormat),
+        };
+        let mut words = line.split(|&c| c == b' ');
+        let event = match (words.next(), words.next(), words.next()) {
+            (Some(b"FULLRESYNC"), Some(replid), Some(offset)) => {
+                self.offset = std::str::from_utf8(offset)?.parse()?;
+                self.state = State::RdbHeader;
+                ReplicationEvent::FullResync {
+                    replid,
+                    offset: self.offset,
+                }
+            }
+            (Some(b"CONTINUE"), replid, None) => {
+                self.state = State::Commands;
+                ReplicationEvent::Continue { replid }
+            }
+            _ => return Err(RespError::IncorrectFormat),
+        };
+        Ok((event, leftover))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn events<'a>(
+        parser: &mut ReplicationParser,
+        mut input: &'a [u8],
+    ) -> Vec<ReplicationEvent<'a>> {
+        let mut events = Vec::new();
+        while !input.is_empty() {
+            let (event, left) = parser.next_event(input).unwrap();
+            events.push(event);
+            input = left;
+        }
+        events
+    }
+
+    #[test]
+    pub fn test_full_resync() {
+        let mut parser = ReplicationParser::new();
+        let input = b"\n\n+FULLRESYNC 8de1787ba490483314a4d30f1c628bc5025eb761 100\r\n$6\r\nREDIS0*1\r\n$4\r\nPING\r\n*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n";
+        assert_eq!(
+            events(&mut parser, input),
+            vec![
+                ReplicationEvent::KeepAlive,
+                ReplicationEvent::FullResync {
+                    replid: b"8de1787ba490483314a4d30f1c628bc5025eb761",
+                    offset: 100
+                },
+                ReplicationEvent::RdbStart { len: Some(6) },
+                ReplicationEvent::RdbChunk(b"REDIS0"),
+                ReplicationEvent::RdbEnd,
+                ReplicationEvent::Command(Resp::Array(vec![Resp::BulkString(b"PING")])),
+                ReplicationEvent::Command(Resp::Array(vec![
+                    Resp::BulkString(b"SET"),
+                    Resp::BulkString(b"k"),
+                    Resp::BulkString(b"v")
+                ])),
+            ]
+        );
+        assert_eq!(parser.offset(), 100 + 14 + 27);
+    }
+
+    #[test]
+    pub fn test_diskless_payload() {
+        let mark = [b'a'; EOF_MARK_LEN];
+        let mut input = b"$EOF:".to_vec();
+        input.extend_from_slice(&mark);
+        input.extend_from_slice(b"\r\nREDIS0011payload");
+        input.extend_from_slice(&mark);
+        input.extend_from_slice(b"*1\r\n$4\r\nPING\r\n");
+
+        // Feed the input a few bytes at a time, keeping the leftover like a reader would
+        let mut parser = ReplicationParser::after_sync();
+        let (mut buffer, mut fed, mut payload) = (Vec::new(), 0, Vec::new());
+        let mut commands = 0;
+        while fed < input.len() || !buffer.is_empty() {
+            match parser.next_event(&buffer) {
+                Ok((event, left)) => {
+                    match event {
+                        ReplicationEvent::RdbChunk(c) => payload.extend_from_slice(c),
+                        ReplicationEvent::Command(_) => commands += 1,
+                        _ => {}
+                    }
+                    buffer = left.to_vec();
+                }
+                Err(RespError::NotEnoughBytes) if fed < input.len() => {
+                    let end = (fed + 7).min(input.len());
+                    buffer.extend_from_slice(&input[fed..end]);
+                    fed = end;
+                }
+                Err(e) => panic!("{:?}", e),
+            }
+        }
+        assert_eq!(payload, b"REDIS0011payload".to_vec());
+        assert_eq!(commands, 1);
+        assert_eq!(parser.offset(), 14);
+    }
+
+    #[test]
+    pub fn test_continue() {
+        let mut parser = ReplicationParser::new();
+        parser.set_offset(500);
+        let input = b"+CONTINUE\r\n*1\r\n$4\r\nPING\r\n";
+        let events = events(&mut parser, input);
+        assert_eq!(events[0], ReplicationEvent::Continue { replid: None });
+        assert_eq!(parser.offset(), 514);
+        let err = ReplicationParser::new()
+            .next_event(b"-NOMASTERLINK Can't SYNC while not connected with my master\r\n")
+            .unwrap_err();
+        assert!(matches!(err, RespError::Other(_)));
+    }
+}
@@ -0,0 +1,227 @@
+//! Splitting the replies of `INFO`, `CLIENT LIST`/`CLIENT INFO` and `CONFIG GET` into their
+//! fields.
+//!
+//! Everything borrows from the reply, values are left as bytes for the caller to convert.
+
+use crate::{Resp, RespError};
+use std::collections::HashMap;
+
+/// The value of an `INFO` field.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum InfoValue<'a> {
+    Plain(&'a [u8]),
+    /// Values made of comma separated `key=value` pairs, like `db0:keys=1,expires=0`. The
+    /// same key can appear more than once (e.g. `bind` in `listener0`).
+    Fields(Vec<(&'a [u8], &'a [u8])>),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InfoSection<'a> {
+    /// The name in the `# Name` header, as sent.
+    pub name: &'a [u8],
+    pub fields: Vec<(&'a [u8], InfoValue<'a>)>,
+}
+
+/// The output of `INFO`, section by section.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Info<'a> {
+    pub sections: Vec<InfoSection<'a>>,
+}
+
+fn split_once(input: &[u8], separator: u8) -> Option<(&[u8], &[u8])> {
+    let index = input.iter().position(|&c| c == separator)?;
+    Some((&input[..index], &input[index + 1..]))
+}
+
+fn lines(input: &[u8]) -> impl Iterator<Item = &[u8]> {
+    input
+        .split(|&c| c == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+}
+
+fn info_value(value: &[u8]) -> InfoValue<'_> {
+    let fields: Option<Vec<_>> = value
+        .split(|&c| c == b',')
+        .map(|f| split_once(f, b'='))
+        .collect();
+    match fields {
+        Some(fields) => InfoValue::Fields(fields),
+        None => InfoValue::Plain(value),
+    }
+}
+
+impl<'a> Info<'a> {
+    /// Parses the text of the reply, failing with [`RespError::IncorrectFormat`] on a line that
+    /// is neither a header nor a `key:value` field.
+    pub fn parse(input: &'a [u8]) -> Result<Self, RespError> {
+        let mut sections: Vec<InfoSection<'a>> = Vec::new();
+        for line in lines(input).filter(|line| !line.is_empty()) {
+            if let Some(name) = line.strip_prefix(b"#") {
+                sections.push(InfoSection {
+                    name: name.strip_prefix(b" ").unwrap_or(name),
+                    fields: Vec::new(),
+                });
+                continue;
+            }
+            let (key, value) = split_once(line, b':').ok_or(RespError::IncorrectFormat)?;
+            // Fields before any header only happen with modules, keep them in a nameless section
+            if sections.is_empty() {
+                sections.push(InfoSection {
+                    name: b"",
+                    fields: Vec::new(),
+                });
+            }
+            let section = sections.last_mut().unwrap();
+            section.fields.push((key, info_value(value)));
+        }
+        Ok(Info { sections })
+    }
+
+    /// The section with the given name, ignoring case.
+    pub fn section(&self, name: &str) -> Option<&InfoSection<'a>> {
+        self.sections
+            .iter()
+            .find(|s| s.name.eq_ignore_ascii_case(name.as_bytes()))
+    }
+
+    /// The first field with the given key, in any section.
+    pub fn get(&self, key: &str) -> Option<&InfoValue<'a>> {
+        self.sections
+            .iter()
+            .flat_map(|s| s.fields.iter())
+            .find(|(k, _)| *k == key.as_bytes())
+            .map(|(_, v)| v)
+    }
+}
+
+impl<'a, 'b> TryFrom<&'b Resp<'a>> for Info<'a> {
+    type Error = RespError;
+
+    /// Accepts the bulk string sent over RESP2 and the verbatim string sent over RESP3.
+    fn try_from(from: &'b Resp<'a>) -> Result<Self, Self::Error> {
+        Info::parse(from.as_bytes().ok_or(RespError::IncorrectFormat)?)
+    }
+}
+
+/// One line of `CLIENT LIST`, or the reply to `CLIENT INFO`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClientInfo<'a> {
+    pub fields: Vec<(&'a [u8], &'a [u8])>,
+}
+
+impl<'a> ClientInfo<'a> {
+    /// Parses a line of space separated `key=value` fields.
+    pub fn parse(line: &'a [u8]) -> Result<Self, RespError> {
+        let fields = line
+            .split(|&c| c == b' ')
+            .filter(|f| !f.is_empty())
+            .map(|f| split_once(f, b'=').ok_or(RespError::IncorrectFormat))
+            .collect::<Result<_, _>>()?;
+        Ok(ClientInfo { fields })
+    }
+
+    /// Parses every line of a `CLIENT LIST` reply.
+    pub fn parse_list(reply: &Resp<'a>) -> Result<Vec<Self>, RespError> {
+        let input = reply.as_bytes().ok_or(RespError::IncorrectFormat)?;
+        lines(input)
+            .filter(|line| !line.is_empty())
+            .map(ClientInfo::parse)
+            .collect()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&'a [u8]> {
+        self.fields
+            .iter()
+            .find(|(k, _)| *k == key.as_bytes())
+            .map(|(_, v)| *v)
+    }
+
+    pub fn id(&self) -> Option<i64> {
+        std::str::from_utf8(self.get("id")?).ok()?.parse().ok()
+    }
+}
+
+impl<'a, 'b> TryFrom<&'b Resp<'a>> for ClientInfo<'a> {
+    type Error = RespError;
+
+    fn try_from(from: &'b Resp<'a>) -> Result<Self, Self::Error> {
+        let input = from.as_bytes().ok_or(RespError::IncorrectFormat)?;
+        ClientInfo::parse(input.strip_suffix(b"\n").unwrap_or(input))
+    }
+}
+
+/// The parameters returned by `CONFIG GET`, from the flat array sent over RESP2 or the map
+/// sent over RESP3.
+pub fn config_map<'a>(reply: &Resp<'a>) -> Result<HashMap<&'a [u8], &'a [u8]>, RespError> {
+    let bytes = |resp: &Resp<'a>| resp.as_bytes().ok_or(RespError::IncorrectFormat);
+    match reply {
+        Resp::Map(m) => m.iter().map(|(k, v)| Ok((bytes(k)?, bytes(v)?))).collect(),
+        Resp::Array(a) if a.len() % 2 == 0 => a
+            .chunks(2)
+            .map(|pair| Ok((bytes(&pair[0])?, bytes(&pair[1])?)))
+            .collect(),
+        _ => Err(RespError::IncorrectFormat),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_resp;
+
+    #[test]
+    pub fn test_info() {
+        let input = b"# Server\r\nredis_version:7.2.4\r\nos:Linux 5.15.0-53-generic x86_64\r\nlistener0:name=tcp,bind=127.0.0.1,bind=-::1,port=6379\r\n\r\n# Modules\r\n\r\n# Keyspace\r\ndb0:keys=1,expires=0,avg_ttl=0\r\n";
+        let reply = Resp::BulkString(input);
+        let info = Info::try_from(&reply).unwrap();
+        assert_eq!(info.sections.len(), 3);
+        assert_eq!(info.get("redis_version"), Some(&InfoValue::Plain(b"7.2.4")));
+        assert_eq!(
+            info.get("os"),
+            Some(&InfoValue::Plain(b"Linux 5.15.0-53-generic x86_64"))
+        );
+        assert_eq!(info.section("modules").unwrap().fields, vec![]);
+        assert_eq!(
+            info.section("Keyspace").unwrap().fields,
+            vec![(
+                &b"db0"[..],
+                InfoValue::Fields(vec![
+                    (&b"keys"[..], &b"1"[..]),
+                    (b"expires", b"0"),
+                    (b"avg_ttl", b"0")
+                ])
+            )]
+        );
+        assert!(matches!(info.get("listener0"), Some(InfoValue::Fields(f)) if f.len() == 4));
+        assert!(Info::parse(b"# Server\r\nnot a field\r\n").is_err());
+    }
+
+    #[test]
+    pub fn test_client_list() {
+        let reply = Resp::BulkString(b"id=3 addr=127.0.0.1:50188 laddr=127.0.0.1:6379 fd=8 name= age=2 cmd=client|list user=default\nid=4 addr=127.0.0.1:50190 laddr=127.0.0.1:6379 fd=9 name=worker age=1 cmd=get user=app\n");
+        let clients = ClientInfo::parse_list(&reply).unwrap();
+        assert_eq!(clients.len(), 2);
+        assert_eq!(clients[0].id(), Some(3));
+        assert_eq!(clients[0].get("name"), Some(&b""[..]));
+        assert_eq!(clients[0].get("cmd"), Some(&b"client|list"[..]));
+        assert_eq!(clients[1].get("name"), Some(&b"worker"[..]));
+        assert_eq!(clients[1].get("missing"), None);
+        let reply = Resp::Verbatim {
+            format: b"txt",
+            text: b"id=5 addr=127.0.0.1:1 name=x\n",
+        };
+        assert_eq!(ClientInfo::try_from(&reply).unwrap().id(), Some(5));
+    }
+
+    #[test]
+    pub fn test_config_map() {
+        let input = b"*4\r\n$9\r\nmaxmemory\r\n$1\r\n0\r\n$10\r\nappendonly\r\n$2\r\nno\r\n";
+        let (reply, _) = parse_resp(input).unwrap();
+        let config = config_map(&reply).unwrap();
+        assert_eq!(config.get(&b"appendonly"[..]), Some(&&b"no"[..]));
+        let input = b"%1\r\n$9\r\nmaxmemory\r\n$1\r\n0\r\n";
+        let (reply, _) = parse_resp(input).unwrap();
+        assert_eq!(config_map(&reply).unwrap().len(), 1);
+        assert!(config_map(&Resp::Array(vec![Resp::BulkString(b"a")])).is_err());
+    }
+}
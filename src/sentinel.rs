@@ -0,0 +1,305 @@
+//! Replies of Redis Sentinel and the events it publishes.
+//!
+//! Sentinel replies with the same shapes over RESP2 and RESP3, except that the descriptions
+//! of instances are maps in RESP3 and flat arrays of field names and values in RESP2. Both
+//! are accepted.
+
+use crate::cluster::Node;
+use crate::pubsub::PubSubMessage;
+use crate::{Resp, RespError};
+
+fn bytes<'a>(resp: &Resp<'a>) -> Result<&'a [u8], RespError> {
+    resp.as_bytes().ok_or(RespError::IncorrectFormat)
+}
+
+fn node(host: &[u8], port: &[u8]) -> Result<Node, RespError> {
+    Ok(Node {
+        host: std::str::from_utf8(host)?.to_string(),
+        port: std::str::from_utf8(port)?.parse()?,
+    })
+}
+
+/// The reply to `SENTINEL GET-MASTER-ADDR-BY-NAME`, `None` when the master is unknown.
+pub fn master_addr(reply: &Resp) -> Result<Option<Node>, RespError> {
+    match reply {
+        Resp::NilArray | Resp::NilBulk | Resp::Null => Ok(None),
+        Resp::Array(a) => match a.as_slice() {
+            [host, port] => node(bytes(host)?, bytes(port)?).map(Some),
+            _ => Err(RespError::IncorrectFormat),
+        },
+        _ => Err(RespError::IncorrectFormat),
+    }
+}
+
+/// The description of a master, replica or sentinel, as returned by `SENTINEL MASTER`, and
+/// for each instance by `SENTINEL MASTERS`, `SENTINEL REPLICAS` and `SENTINEL SENTINELS`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SentinelInstance<'a> {
+    pub fields: Vec<(&'a [u8], &'a [u8])>,
+}
+
+impl<'a> SentinelInstance<'a> {
+    /// Parses the reply to `SENTINEL MASTERS` and the like.
+    pub fn parse_list(reply: &Resp<'a>) -> Result<Vec<Self>, RespError> {
+        match reply {
+            Resp::Array(a) => a.iter().map(SentinelInstance::try_from).collect(),
+            _ => Err(RespError::IncorrectFormat),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&'a [u8]> {
+        self.fields
+            .iter()
+            .find(|(k, _)| *k == key.as_bytes())
+            .map(|(_, v)| *v)
+    }
+
+    pub fn name(&self) -> Option<&'a [u8]> {
+        self.get("name")
+    }
+
+    pub fn addr(&self) -> Option<Node> {
+        node(self.get("ip")?, self.get("port")?).ok()
+    }
+
+    /// The flags, such as `master`, `slave`, `s_down`, `o_down` or `disconnected`.
+    pub fn flags(&self) -> impl Iterator<Item = &'a [u8]> {
+        self.get("flags")
+            .unwrap_or_default()
+            .split(|&c| c == b',')
+            .filter(|flag| !flag.is_empty())
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags().any(|f| f == flag.as_bytes())
+    }
+
+    /// Whether the instance is subjectively or objectively down, or disconnected.
+    pub fn is_down(&self) -> bool {
+        self.flags()
+            .any(|f| matches!(f, b"s_down" | b"o_down" | b"disconnected"))
+    }
+
+    /// The integer value of a field, like `num-slaves`, `quorum` or `link-pending-commands`.
+    pub fn get_integer(&self, key: &str) -> Option<i64> {
+        std::str::from_utf8(self.get(key)?).ok()?.parse().ok()
+    }
+}
+
+impl<'a, 'b> TryFrom<&'b Resp<'a>> for SentinelInstance<'a> {
+    type Error = RespError;
+
+    fn try_from(from: &'b Resp<'a>) -> Result<Self, Self::Error> {
+        let fields = match from {
+            Resp::Map(m) => m
+                .iter()
+                .map(|(k, v)| Ok((bytes(k)?, bytes(v)?)))
+                .collect::<Result<_, RespError>>()?,
+            Resp::Array(a) if a.len() % 2 == 0 => a
+                .chunks(2)
+                .map(|pair| Ok((bytes(&pair[0])?, bytes(&pair[1])?)))
+                .collect::<Result<_, RespError>>()?,
+            _ => return Err(RespError::IncorrectFormat),
+        };
+        Ok(SentinelInstance { fields })
+    }
+}
+
+/// The instance an event is about, from the `<type> <name> <ip> <port> @ <master name>
+/// <master ip> <master port>` payloads. The master part is absent when the instance is a
+/// master.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstanceDetails<'a> {
+    /// `master`, `slave` or `sentinel`.
+    pub kind: &'a [u8],
+    pub name: &'a [u8],
+    pub addr: Node,
+    pub master: Option<(&'a [u8], Node)>,
+    /// What follows the details in some events, like `#quorum 2/2` for `+odown`.
+    pub extra: Vec<&'a [u8]>,
+}
+
+impl<'a> InstanceDetails<'a> {
+    pub fn parse(payload: &'a [u8]) -> Result<Self, RespError> {
+        let words: Vec<&[u8]> = payload.split(|&c| c == b' ').collect();
+        let (details, rest) = match words.as_slice() {
+            [kind, name, host, port, rest @ ..] => ((*kind, *name, node(host, port)?), rest),
+            _ => return Err(RespError::IncorrectFormat),
+        };
+        let (master, extra) = match rest {
+            [b"@", name, host, port, extra @ ..] => (Some((*name, node(host, port)?)), extra),
+            [b"@", ..] => return Err(RespError::IncorrectFormat),
+            extra => (None, extra),
+        };
+        let (kind, name, addr) = details;
+        Ok(InstanceDetails {
+            kind,
+            name,
+            addr,
+            master,
+            extra: extra.to_vec(),
+        })
+    }
+}
+
+/// An event published by Sentinel, on the channel named after the event.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SentinelEvent<'a> {
+    /// `+switch-master`: a failover completed and clients should connect to `new`.
+    SwitchMaster {
+        master: &'a [u8],
+        old: Node,
+        new: Node,
+    },
+    /// Events about an instance, like `+sdown`, `-odown`, `+slave` or `+failover-end`.
+    Instance {
+        event: &'a [u8],
+        details: InstanceDetails<'a>,
+    },
+    /// Events with another payload, like `+new-epoch` or `+tilt`.
+    Other { event: &'a [u8], payload: &'a [u8] },
+}
+
+impl<'a> SentinelEvent<'a> {
+    /// Parses an event from the channel it was published on and its payload.
+    pub fn parse(channel: &'a [u8], payload: &'a [u8]) -> Result<Self, RespError> {
+        if channel == b"+switch-master" {
+            let words: Vec<&[u8]> = payload.split(|&c| c == b' ').collect();
+            return match words.as_slice() {
+                [master, old_host, old_port, new_host, new_port] => {
+                    Ok(SentinelEvent::SwitchMaster {
+                        master,
+                        old: node(old_host, old_port)?,
+                        new: node(new_host, new_port)?,
+                    })
+                }
+                _ => Err(RespError::IncorrectFormat),
+            };
+        }
+        let event = match InstanceDetails::parse(payload) {
+            Ok(details) if matches!(details.kind, b"master" | b"slave" | b"sentinel") => {
+                SentinelEvent::Instance {
+                    event: channel,
+                    details,
+                }
+            }
+            _ => SentinelEvent::Other {
+                event: channel,
+                payload,
+            },
+        };
+        Ok(event)
+    }
+}
+
+impl<'a, 'b> TryFrom<&'b PubSubMessage<'a>> for SentinelEvent<'a> {
+    type Error = RespError;
+
+    /// Accepts the messages of a `SUBSCRIBE` to some events or of a `PSUBSCRIBE *`.
+    fn try_from(from: &'b PubSubMessage<'a>) -> Result<Self, Self::Error> {
+        match from {
+            PubSubMessage::Message { channel, payload }
+            | PubSubMessage::PMessage {
+                channel, payload, ..
+            } => SentinelEvent::parse(channel, payload),
+            _ => Err(RespError::IncorrectFormat),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_resp;
+
+    fn node(host: &str, port: u16) -> Node {
+        Node {
+            host: host.to_string(),
+            port,
+        }
+    }
+
+    #[test]
+    pub fn test_master_addr() {
+        let (reply, _) = parse_resp(b"*2\r\n$9\r\n127.0.0.1\r\n$4\r\n6379\r\n").unwrap();
+        assert_eq!(master_addr(&reply).unwrap(), Some(node("127.0.0.1", 6379)));
+        assert_eq!(master_addr(&Resp::NilArray).unwrap(), None);
+        assert_eq!(master_addr(&Resp::Null).unwrap(), None);
+        let (reply, _) = parse_resp(b"*2\r\n$9\r\n127.0.0.1\r\n$3\r\nabc\r\n").unwrap();
+        assert!(master_addr(&reply).is_err());
+    }
+
+    #[test]
+    pub fn test_instances() {
+        let input = b"*1\r\n*10\r\n$4\r\nname\r\n$8\r\nmymaster\r\n$2\r\nip\r\n$9\r\n127.0.0.1\r\n$4\r\nport\r\n$4\r\n6379\r\n$5\r\nflags\r\n$13\r\nmaster,s_down\r\n$10\r\nnum-slaves\r\n$1\r\n2\r\n";
+        let (reply, _) = parse_resp(input).unwrap();
+        let masters = SentinelInstance::parse_list(&reply).unwrap();
+        assert_eq!(masters.len(), 1);
+        let master = &masters[0];
+        assert_eq!(master.name(), Some(&b"mymaster"[..]));
+        assert_eq!(master.addr(), Some(node("127.0.0.1", 6379)));
+        assert_eq!(
+            master.flags().collect::<Vec<_>>(),
+            vec![&b"master"[..], b"s_down"]
+        );
+        assert!(master.has_flag("master"));
+        assert!(master.is_down());
+        assert_eq!(master.get_integer("num-slaves"), Some(2));
+
+        let input = b"%2\r\n$2\r\nip\r\n$8\r\n10.0.0.2\r\n$4\r\nport\r\n$4\r\n6380\r\n";
+        let (reply, _) = parse_resp(input).unwrap();
+        let replica = SentinelInstance::try_from(&reply).unwrap();
+        assert_eq!(replica.addr(), Some(node("10.0.0.2", 6380)));
+        assert!(!replica.is_down());
+        assert!(SentinelInstance::try_from(&Resp::Integer(b"1")).is_err());
+    }
+
+    #[test]
+    pub fn test_events() {
+        let input = b"*3\r\n$7\r\nmessage\r\n$14\r\n+switch-master\r\n$46\r\nmymaster 127.0.0.1 6379 127.0.0.1 6380 extra!!\r\n";
+        let (reply, _) = parse_resp(input).unwrap();
+        let message = PubSubMessage::try_from(&reply).unwrap();
+        assert!(SentinelEvent::try_from(&message).is_err());
+
+        let event =
+            SentinelEvent::parse(b"+switch-master", b"mymaster 127.0.0.1 6379 127.0.0.1 6380")
+                .unwrap();
+        assert_eq!(
+            event,
+            SentinelEvent::SwitchMaster {
+                master: b"mymaster",
+                old: node("127.0.0.1", 6379),
+                new: node("127.0.0.1", 6380)
+            }
+        );
+
+        let event = SentinelEvent::parse(
+            b"+sdown",
+            b"slave 127.0.0.1:6380 127.0.0.1 6380 @ mymaster 127.0.0.1 6379",
+        )
+        .unwrap();
+        let SentinelEvent::Instance { event, details } = event else {
+            panic!("{:?}", event)
+        };
+        assert_eq!(event, b"+sdown");
+        assert_eq!(details.kind, b"slave");
+        assert_eq!(details.addr, node("127.0.0.1", 6380));
+        assert_eq!(
+            details.master,
+            Some((&b"mymaster"[..], node("127.0.0.1", 6379)))
+        );
+
+        let event =
+            SentinelEvent::parse(b"+odown", b"master mymaster 127.0.0.1 6379 #quorum 2/2").unwrap();
+        assert!(
+            matches!(event, SentinelEvent::Instance { details, .. } if details.extra == vec![&b"#quorum"[..], b"2/2"])
+        );
+        assert_eq!(
+            SentinelEvent::parse(b"+new-epoch", b"5").unwrap(),
+            SentinelEvent::Other {
+                event: b"+new-epoch",
+                payload: b"5"
+            }
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! The accept/read/dispatch loop of a RESP service, leaving only the commands to implement.
+//!
+//! Every connection gets its own thread and its own [`Handler`]. Requests are read with
+//! [`RespReader`], and replies to pipelined requests are written together once every complete
+//! request read so far has been answered.
+
+use crate::reader::RespReader;
+use crate::{OwnedResp, Resp, RespError};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+/// A request, as the list of its arguments starting with the command name.
+///
+/// Both the usual arrays of bulk strings and inline commands (a line of space separated
+/// arguments, as typed in telnet) are accepted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Command {
+    args: Vec<Vec<u8>>,
+}
+
+impl Command {
+    /// Fails if there is no command name.
+    pub fn new(args: Vec<Vec<u8>>) -> Result<Self, RespError> {
+        if args.is_empty() {
+            return Err(RespError::IncorrectFormat);
+        }
+        Ok(Self { args })
+    }
+
+    /// The command name, as sent by the client.
+    pub fn name(&self) -> &[u8] {
+        &self.args[0]
+    }
+
+    /// Whether the command name is `name`, ignoring case.
+    pub fn is(&self, name: &str) -> bool {
+        self.name().eq_ignore_ascii_case(name.as_bytes())
+    }
+
+    /// The arguments following the command name.
+    pub fn args(&self) -> &[Vec<u8>] {
+        &self.args[1..]
+    }
+
+    pub fn into_args(self) -> Vec<Vec<u8>> {
+        self.args
+    }
+}
+
+impl TryFrom<OwnedResp> for Command {
+    type Error = RespError;
+
+    fn try_from(from: OwnedResp) -> Result<Self, Self::Error> {
+        let args = match from {
+            OwnedResp::Array(a) => a
+                .into_iter()
+                .map(|arg| match arg {
+                    OwnedResp::BulkString(arg) => Ok(arg),
+                    _ => Err(RespError::IncorrectFormat),
+                })
+                .collect::<Result<_, _>>()?,
+            OwnedResp::String(line) => line
+                .split(u8::is_ascii_whitespace)
+                .filter(|arg| !arg.is_empty())
+                .map(<[u8]>::to_vec)
+                .collect(),
+            _ => return Err(RespError::IncorrectFormat),
+        };
+        Command::new(args)
+    }
+}
+
+/// Implements the commands of a service.
+///
+/// The command is passed by reference so that the reply can borrow from it as well as from the
+/// handler, e.g. to echo an argument back or to return a stored value without copying it.
+pub trait Handler {
+    fn call<'a>(&'a mut self, cmd: &'a Command) -> Resp<'a>;
+}
+
+// Blank inline lines and empty arrays are ignored rather than answered.
+fn is_blank(frame: &OwnedResp) -> bool {
+    match frame {
+        OwnedResp::Array(a) => a.is_empty(),
+        OwnedResp::String(line) => line.iter().all(u8::is_ascii_whitespace),
+        _ => false,
+    }
+}
+
+// Whether reading failed because of the connection rather than because of what was sent on it.
+fn is_io_error(err: &RespError) -> bool {
+    matches!(err, RespError::Other(err) if err.is::<std::io::Error>())
+}
+
+/// Serves requests from a single connection until the client disconnects.
+///
+/// A request that cannot be parsed gets an error reply, after which the connection is dropped
+/// since there is no telling where the next request starts.
+pub fn serve_connection<S, H>(stream: S, handler: &mut H) -> Result<(), RespError>
+where
+    S: Read + Write,
+    H: Handler,
+{
+    let mut reader = RespReader::new(stream);
+    let mut output = Vec::new();
+    loop {
+        // Only write once there is no other complete request waiting to be answered
+        let frame = match reader.read_buffered_frame() {
+            Ok(None) => {
+                if !output.is_empty() {
+                    let stream = reader.get_mut();
+                    stream.write_all(&output)?;
+                    stream.flush()?;
+                    output.clear();
+                }
+                reader.read_frame()
+            }
+            result => result,
+        };
+        let frame = match frame {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return Ok(()),
+            Err(err) => {
+                if !is_io_error(&err) {
+                    output.extend_from_slice(b"-ERR Protocol error\r\n");
+                    reader.get_mut().write_all(&output)?;
+                }
+                return Err(err);
+            }
+        };
+        if !is_blank(&frame) {
+            match Command::try_from(frame) {
+                Ok(cmd) => handler.call(&cmd).write_to_writer(&mut output)?,
+                Err(_) => output.extend_from_slice(
+                    b"-ERR Protocol error: expected an array of bulk strings\r\n",
+                ),
+            }
+        }
+    }
+}
+
+/// Accepts connections forever, serving each one on its own thread with a handler made by
+/// `new_handler`.
+///
+/// Handlers sharing state between connections can hold it behind an `Arc`. Errors on a single
+/// connection only end that connection.
+pub fn serve<F, H>(listener: TcpListener, new_handler: F) -> Result<(), RespError>
+where
+    F: Fn() -> H,
+    H: Handler + Send + 'static,
+{
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let mut handler = new_handler();
+        thread::spawn(move || {
+            let _ = serve_connection(stream, &mut handler);
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::Client;
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    #[derive(Default)]
+    struct Store {
+        values: HashMap<Vec<u8>, Vec<u8>>,
+    }
+
+    impl Handler for Store {
+        fn call<'a>(&'a mut self, cmd: &'a Command) -> Resp<'a> {
+            match (cmd.name().to_ascii_uppercase().as_slice(), cmd.args()) {
+                (b"PING", []) => Resp::String(b"PONG"),
+                (b"ECHO", [message]) => Resp::BulkString(message),
+                (b"SET", [key, value]) => {
+                    self.values.insert(key.clone(), value.clone());
+                    Resp::String(b"OK")
+                }
+                (b"GET", [key]) => match self.values.get(key) {
+                    Some(value) => Resp::BulkString(value),
+                    None => Resp::NilBulk,
+                },
+                _ => Resp::Error(b"ERR unknown command"),
+            }
+        }
+    }
+
+    /// A stream reading from fixed input and keeping what is written to it.
+    struct Duplex {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+        writes: usize,
+    }
+
+    impl Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writes += 1;
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run(input: &[u8]) -> (Result<(), RespError>, Duplex) {
+        let mut stream = Duplex {
+            input: Cursor::new(input.to_vec()),
+            output: Vec::new(),
+            writes: 0,
+        };
+        let result = serve_connection(&mut stream, &mut Store::default());
+        (result, stream)
+    }
+
+    #[test]
+    pub fn test_command() {
+        let frame = OwnedResp::String(b" SET  k v ".to_vec());
+        let cmd = Command::try_from(frame).unwrap();
+        assert!(cmd.is("set"));
+        assert_eq!(cmd.args(), &[b"k".to_vec(), b"v".to_vec()]);
+        let frame = OwnedResp::Array(vec![OwnedResp::Integer(b"1".to_vec())]);
+        assert!(Command::try_from(frame).is_err());
+        assert!(Command::try_from(OwnedResp::Array(vec![])).is_err());
+    }
+
+    #[test]
+    pub fn test_pipelined_requests() {
+        let input =
+            b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n*2\r\n$3\r\nGET\r\n$1\r\nk\r\n\r\nPING\r\n";
+        let (result, stream) = run(input);
+        result.unwrap();
+        assert_eq!(stream.output, b"+OK\r\n$1\r\nv\r\n+PONG\r\n".to_vec());
+        assert_eq!(stream.writes, 1);
+    }
+
+    #[test]
+    pub fn test_protocol_errors() {
+        let (result, stream) = run(b"*1\r\n:1\r\n*1\r\n$4\r\nPING\r\n$3\r\nabcdef\r\n");
+        assert!(matches!(result, Err(RespError::IncorrectFormat)));
+        assert_eq!(
+            stream.output,
+            b"-ERR Protocol error: expected an array of bulk strings\r\n+PONG\r\n-ERR Protocol error\r\n".to_vec()
+        );
+    }
+
+    #[test]
+    pub fn test_invalid_lengths() {
+        let (result, stream) = run(b"*1\r\n$4\r\nPING\r\n*1\r\n$abc\r\n");
+        assert!(matches!(result, Err(RespError::Other(_))));
+        assert_eq!(stream.output, b"+PONG\r\n-ERR Protocol error\r\n".to_vec());
+        // A client going away in the middle of a request is not answered
+        let (result, stream) = run(b"*1\r\n$4\r\nPI");
+        assert!(matches!(result, Err(RespError::Other(_))));
+        assert!(stream.output.is_empty());
+    }
+
+    #[test]
+    pub fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let _ = serve(listener, Store::default);
+        });
+        let mut client = Client::connect(addr).unwrap();
+        let replies = client
+            .pipeline()
+            .cmd(&["SET", "k", "v"])
+            .cmd(&["ECHO", "hi"])
+            .cmd(&["GET", "k"])
+            .execute()
+            .unwrap();
+        assert_eq!(
+            replies,
+            vec![
+                OwnedResp::String(b"OK".to_vec()),
+                OwnedResp::BulkString(b"hi".to_vec()),
+                OwnedResp::BulkString(b"v".to_vec()),
+            ]
+        );
+        // Every connection has its own handler
+        let mut client = Client::connect(addr).unwrap();
+        assert_eq!(client.send(&["GET", "k"]).unwrap(), OwnedResp::NilBulk);
+    }
+}
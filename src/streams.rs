@@ -0,0 +1,358 @@
+//! Typed views of the replies of the stream commands.
+//!
+//! Both protocols are accepted: RESP3 sends maps where RESP2 sends flat arrays of keys and
+//! values, and nulls where RESP2 sends nil arrays or nil bulk strings.
+
+use crate::{Resp, RespError};
+use std::fmt;
+use std::str::FromStr;
+
+/// The id of a stream entry, `<milliseconds>-<sequence>`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct StreamId {
+    pub ms: u64,
+    pub seq: u64,
+}
+
+impl FromStr for StreamId {
+    type Err = RespError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ms, seq) = s.split_once('-').ok_or(RespError::IncorrectFormat)?;
+        Ok(StreamId {
+            ms: ms.parse()?,
+            seq: seq.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for StreamId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.ms, self.seq)
+    }
+}
+
+impl TryFrom<&Resp<'_>> for StreamId {
+    type Error = RespError;
+
+    fn try_from(from: &Resp<'_>) -> Result<Self, Self::Error> {
+        std::str::from_utf8(bytes(from)?)?.parse()
+    }
+}
+
+fn bytes<'a>(resp: &Resp<'a>) -> Result<&'a [u8], RespError> {
+    resp.as_bytes().ok_or(RespError::IncorrectFormat)
+}
+
+// Counts are integers, except in the consumers of `XPENDING` where they are bulk strings.
+fn count(resp: &Resp) -> Result<u64, RespError> {
+    let count = match resp.as_integer() {
+        Some(count) => count,
+        None => std::str::from_utf8(bytes(resp)?)?.parse()?,
+    };
+    u64::try_from(count).map_err(|_| RespError::IncorrectFormat)
+}
+
+fn is_nil(resp: &Resp) -> bool {
+    matches!(resp, Resp::NilBulk | Resp::NilArray | Resp::Null)
+}
+
+fn optional_id(resp: &Resp) -> Result<Option<StreamId>, RespError> {
+    match resp {
+        resp if is_nil(resp) => Ok(None),
+        resp => StreamId::try_from(resp).map(Some),
+    }
+}
+
+fn array<'r, 'a>(resp: &'r Resp<'a>) -> Result<&'r [Resp<'a>], RespError> {
+    match resp {
+        Resp::Array(a) => Ok(a),
+        _ => Err(RespError::IncorrectFormat),
+    }
+}
+
+// The entries of a map, sent as a flat array over RESP2.
+fn pairs<'r, 'a>(resp: &'r Resp<'a>) -> Result<Vec<(&'r Resp<'a>, &'r Resp<'a>)>, RespError> {
+    match resp {
+        Resp::Map(m) => Ok(m.iter().map(|(k, v)| (k, v)).collect()),
+        Resp::Array(a) if a.len() % 2 == 0 => {
+            Ok(a.chunks(2).map(|pair| (&pair[0], &pair[1])).collect())
+        }
+        _ => Err(RespError::IncorrectFormat),
+    }
+}
+
+/// An entry, as returned by `XRANGE`, `XREAD`, `XCLAIM`...
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StreamEntry<'a> {
+    pub id: StreamId,
+    /// `None` for an entry deleted while still pending, which `XREADGROUP` reading the
+    /// history of a consumer, `XCLAIM` and `XAUTOCLAIM` return without its fields.
+    pub fields: Option<Vec<(&'a [u8], &'a [u8])>>,
+}
+
+impl<'a> StreamEntry<'a> {
+    /// Parses a list of entries, like the reply to `XRANGE`.
+    pub fn parse_list(reply: &Resp<'a>) -> Result<Vec<Self>, RespError> {
+        array(reply)?.iter().map(StreamEntry::try_from).collect()
+    }
+
+    pub fn get(&self, field: &[u8]) -> Option<&'a [u8]> {
+        self.fields
+            .iter()
+            .flatten()
+            .find(|(f, _)| *f == field)
+            .map(|(_, v)| *v)
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.fields.is_none()
+    }
+}
+
+impl<'a, 'b> TryFrom<&'b Resp<'a>> for StreamEntry<'a> {
+    type Error = RespError;
+
+    fn try_from(from: &'b Resp<'a>) -> Result<Self, Self::Error> {
+        match array(from)? {
+            [id, fields] if is_nil(fields) => Ok(StreamEntry {
+                id: StreamId::try_from(id)?,
+                fields: None,
+            }),
+            [id, fields] => Ok(StreamEntry {
+                id: StreamId::try_from(id)?,
+                fields: Some(
+                    pairs(fields)?
+                        .into_iter()
+                        .map(|(f, v)| Ok((bytes(f)?, bytes(v)?)))
+                        .collect::<Result<_, RespError>>()?,
+                ),
+            }),
+            _ => Err(RespError::IncorrectFormat),
+        }
+    }
+}
+
+/// The reply to `XREAD` or `XREADGROUP`, with the new entries of each stream.
+///
+/// A blocking read that timed out has no streams.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct XReadReply<'a> {
+    pub streams: Vec<(&'a [u8], Vec<StreamEntry<'a>>)>,
+}
+
+impl<'a, 'b> TryFrom<&'b Resp<'a>> for XReadReply<'a> {
+    type Error = RespError;
+
+    fn try_from(from: &'b Resp<'a>) -> Result<Self, Self::Error> {
+        let streams: Vec<(&Resp<'a>, &Resp<'a>)> = match from {
+            resp if is_nil(resp) => Vec::new(),
+            Resp::Map(m) => m.iter().map(|(k, v)| (k, v)).collect(),
+            Resp::Array(a) => a
+                .iter()
+                .map(|stream| match array(stream)? {
+                    [name, entries] => Ok((name, entries)),
+                    _ => Err(RespError::IncorrectFormat),
+                })
+                .collect::<Result<_, RespError>>()?,
+            _ => return Err(RespError::IncorrectFormat),
+        };
+        let streams = streams
+            .into_iter()
+            .map(|(name, entries)| Ok((bytes(name)?, StreamEntry::parse_list(entries)?)))
+            .collect::<Result<_, RespError>>()?;
+        Ok(XReadReply { streams })
+    }
+}
+
+/// The reply to the summary form of `XPENDING`, without a range.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct XPendingSummary<'a> {
+    pub count: u64,
+    /// `None` when nothing is pending.
+    pub smallest: Option<StreamId>,
+    pub largest: Option<StreamId>,
+    /// How many entries each consumer has pending.
+    pub consumers: Vec<(&'a [u8], u64)>,
+}
+
+impl<'a, 'b> TryFrom<&'b Resp<'a>> for XPendingSummary<'a> {
+    type Error = RespError;
+
+    fn try_from(from: &'b Resp<'a>) -> Result<Self, Self::Error> {
+        match array(from)? {
+            [pending, smallest, largest, consumers] => Ok(XPendingSummary {
+                count: count(pending)?,
+                smallest: optional_id(smallest)?,
+                largest: optional_id(largest)?,
+                consumers: match consumers {
+                    consumers if is_nil(consumers) => Vec::new(),
+                    consumers => array(consumers)?
+                        .iter()
+                        .map(|consumer| match array(consumer)? {
+                            [name, pending] => Ok((bytes(name)?, count(pending)?)),
+                            _ => Err(RespError::IncorrectFormat),
+                        })
+                        .collect::<Result<_, RespError>>()?,
+                },
+            }),
+            _ => Err(RespError::IncorrectFormat),
+        }
+    }
+}
+
+/// The reply to `XINFO STREAM`, without `FULL`.
+///
+/// The fields added in Redis 7 are `None` when talking to older servers.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct XInfoStream<'a> {
+    pub length: u64,
+    pub radix_tree_keys: u64,
+    pub radix_tree_nodes: u64,
+    pub groups: u64,
+    pub last_generated_id: StreamId,
+    pub max_deleted_entry_id: Option<StreamId>,
+    pub entries_added: Option<u64>,
+    pub recorded_first_entry_id: Option<StreamId>,
+    pub first_entry: Option<StreamEntry<'a>>,
+    pub last_entry: Option<StreamEntry<'a>>,
+}
+
+impl<'a, 'b> TryFrom<&'b Resp<'a>> for XInfoStream<'a> {
+    type Error = RespError;
+
+    fn try_from(from: &'b Resp<'a>) -> Result<Self, Self::Error> {
+        let entry = |resp: &Resp<'a>| match resp {
+            resp if is_nil(resp) => Ok(None),
+            resp => StreamEntry::try_from(resp).map(Some),
+        };
+        let mut info = XInfoStream::default();
+        let mut last_generated_id = None;
+        for (key, value) in pairs(from)? {
+            match bytes(key)? {
+                b"length" => info.length = count(value)?,
+                b"radix-tree-keys" => info.radix_tree_keys = count(value)?,
+                b"radix-tree-nodes" => info.radix_tree_nodes = count(value)?,
+                b"groups" => info.groups = count(value)?,
+                b"last-generated-id" => last_generated_id = Some(StreamId::try_from(value)?),
+                b"max-deleted-entry-id" => info.max_deleted_entry_id = optional_id(value)?,
+                b"entries-added" => info.entries_added = Some(count(value)?),
+                b"recorded-first-entry-id" => info.recorded_first_entry_id = optional_id(value)?,
+                b"first-entry" => info.first_entry = entry(value)?,
+                b"last-entry" => info.last_entry = entry(value)?,
+                _ => {}
+            }
+        }
+        info.last_generated_id = last_generated_id.ok_or(RespError::IncorrectFormat)?;
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_resp;
+
+    fn parse(input: &[u8]) -> Resp<'_> {
+        let (resp, left) = parse_resp(input).unwrap();
+        assert!(left.is_empty());
+        resp
+    }
+
+    #[test]
+    pub fn test_stream_id() {
+        let id: StreamId = "1526919030474-55".parse().unwrap();
+        assert_eq!(
+            id,
+            StreamId {
+                ms: 1526919030474,
+                seq: 55
+            }
+        );
+        assert_eq!(id.to_string(), "1526919030474-55");
+        assert!("1526919030474".parse::<StreamId>().is_err());
+        assert!(StreamId { ms: 1, seq: 9 } < StreamId { ms: 2, seq: 0 });
+    }
+
+    #[test]
+    pub fn test_xrange() {
+        let reply = parse(b"*2\r\n*2\r\n$3\r\n1-0\r\n*4\r\n$1\r\na\r\n$1\r\n1\r\n$1\r\nb\r\n$1\r\n2\r\n*2\r\n$3\r\n2-0\r\n*0\r\n");
+        let entries = StreamEntry::parse_list(&reply).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, StreamId { ms: 1, seq: 0 });
+        assert_eq!(entries[0].get(b"b"), Some(&b"2"[..]));
+        assert_eq!(entries[1].fields, Some(vec![]));
+        assert!(StreamEntry::parse_list(&parse(b"*1\r\n*1\r\n$3\r\n1-0\r\n")).is_err());
+
+        // Deleted while pending, as XREADGROUP returns it from the history of a consumer
+        let reply = parse(b"*1\r\n*2\r\n$3\r\n3-0\r\n*-1\r\n");
+        let entries = StreamEntry::parse_list(&reply).unwrap();
+        assert_eq!(entries[0].id, StreamId { ms: 3, seq: 0 });
+        assert!(entries[0].is_deleted());
+        assert_eq!(entries[0].get(b"a"), None);
+        let entry = StreamEntry::try_from(&parse(b"*2\r\n$3\r\n3-0\r\n_\r\n")).unwrap();
+        assert!(entry.is_deleted());
+    }
+
+    #[test]
+    pub fn test_xread() {
+        let entry = b"*2\r\n$3\r\n1-1\r\n*2\r\n$1\r\nf\r\n$1\r\nv\r\n";
+        let resp2 = [&b"*1\r\n*2\r\n$1\r\ns\r\n*1\r\n"[..], entry].concat();
+        let resp3 = [&b"%1\r\n$1\r\ns\r\n*1\r\n"[..], entry].concat();
+        let expected = XReadReply {
+            streams: vec![(
+                b"s",
+                vec![StreamEntry {
+                    id: StreamId { ms: 1, seq: 1 },
+                    fields: Some(vec![(b"f", b"v")]),
+                }],
+            )],
+        };
+        assert_eq!(XReadReply::try_from(&parse(&resp2)).unwrap(), expected);
+        assert_eq!(XReadReply::try_from(&parse(&resp3)).unwrap(), expected);
+        assert_eq!(
+            XReadReply::try_from(&parse(b"*-1\r\n")).unwrap(),
+            XReadReply::default()
+        );
+        assert_eq!(
+            XReadReply::try_from(&parse(b"_\r\n")).unwrap(),
+            XReadReply::default()
+        );
+    }
+
+    #[test]
+    pub fn test_xpending() {
+        let reply = parse(b"*4\r\n:3\r\n$3\r\n1-0\r\n$3\r\n5-0\r\n*2\r\n*2\r\n$5\r\nalice\r\n$1\r\n2\r\n*2\r\n$3\r\nbob\r\n$1\r\n1\r\n");
+        assert_eq!(
+            XPendingSummary::try_from(&reply).unwrap(),
+            XPendingSummary {
+                count: 3,
+                smallest: Some(StreamId { ms: 1, seq: 0 }),
+                largest: Some(StreamId { ms: 5, seq: 0 }),
+                consumers: vec![(b"alice", 2), (b"bob", 1)],
+            }
+        );
+        let reply = parse(b"*4\r\n:0\r\n_\r\n_\r\n_\r\n");
+        let summary = XPendingSummary::try_from(&reply).unwrap();
+        assert_eq!(summary.smallest, None);
+        assert!(summary.consumers.is_empty());
+    }
+
+    #[test]
+    pub fn test_xinfo_stream() {
+        let resp3 = parse(b"%10\r\n+length\r\n:2\r\n+radix-tree-keys\r\n:1\r\n+radix-tree-nodes\r\n:2\r\n+last-generated-id\r\n$3\r\n2-0\r\n+max-deleted-entry-id\r\n$3\r\n0-0\r\n+entries-added\r\n:2\r\n+recorded-first-entry-id\r\n$3\r\n1-0\r\n+groups\r\n:1\r\n+first-entry\r\n*2\r\n$3\r\n1-0\r\n*2\r\n$1\r\na\r\n$1\r\n1\r\n+last-entry\r\n_\r\n");
+        let info = XInfoStream::try_from(&resp3).unwrap();
+        assert_eq!(info.length, 2);
+        assert_eq!(info.last_generated_id, StreamId { ms: 2, seq: 0 });
+        assert_eq!(info.entries_added, Some(2));
+        assert_eq!(info.first_entry.unwrap().get(b"a"), Some(&b"1"[..]));
+        assert_eq!(info.last_entry, None);
+
+        // Redis 6 over RESP2
+        let resp2 = parse(b"*14\r\n$6\r\nlength\r\n:0\r\n$15\r\nradix-tree-keys\r\n:1\r\n$16\r\nradix-tree-nodes\r\n:2\r\n$6\r\ngroups\r\n:0\r\n$17\r\nlast-generated-id\r\n$3\r\n0-0\r\n$11\r\nfirst-entry\r\n$-1\r\n$10\r\nlast-entry\r\n$-1\r\n");
+        let info = XInfoStream::try_from(&resp2).unwrap();
+        assert_eq!(info.entries_added, None);
+        assert_eq!(info.first_entry, None);
+        assert!(XInfoStream::try_from(&parse(b"*0\r\n")).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+//! Client side caching, with the invalidation messages sent after `CLIENT TRACKING ON`.
+//!
+//! Over RESP3 invalidations are push frames on the tracking connection itself. Over RESP2
+//! they are published on [`INVALIDATION_CHANNEL`] to a connection subscribed to it, which the
+//! tracking connection redirects to with `CLIENT TRACKING ON REDIRECT <id>`.
+
+use crate::pubsub::raw_message;
+use crate::{Resp, RespError};
+use std::collections::HashMap;
+
+/// The channel invalidations are published on over RESP2.
+pub const INVALIDATION_CHANNEL: &str = "__redis__:invalidate";
+
+/// Keys whose cached values are stale.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Invalidation<'a> {
+    Keys(Vec<&'a [u8]>),
+    /// Every key, after a `FLUSHALL` or `FLUSHDB`.
+    All,
+}
+
+fn keys<'a>(resp: &Resp<'a>) -> Result<Invalidation<'a>, RespError> {
+    match resp {
+        Resp::NilBulk | Resp::NilArray | Resp::Null => Ok(Invalidation::All),
+        Resp::Array(keys) => keys
+            .iter()
+            .map(|key| key.as_bytes().ok_or(RespError::IncorrectFormat))
+            .collect::<Result<_, _>>()
+            .map(Invalidation::Keys),
+        _ => Err(RespError::IncorrectFormat),
+    }
+}
+
+impl<'a, 'b> TryFrom<&'b Resp<'a>> for Invalidation<'a> {
+    type Error = RespError;
+
+    /// Accepts the RESP3 `invalidate` push and the RESP2 message on
+    /// [`INVALIDATION_CHANNEL`], whose payload is an array rather than a bulk string.
+    fn try_from(from: &'b Resp<'a>) -> Result<Self, Self::Error> {
+        match from {
+            Resp::Push(a) => match a.as_slice() {
+                [kind, payload] if kind.as_bytes() == Some(b"invalidate") => keys(payload),
+                _ => Err(RespError::IncorrectFormat),
+            },
+            Resp::Array(_) => match raw_message(from)? {
+                (channel, payload) if channel == INVALIDATION_CHANNEL.as_bytes() => keys(payload),
+                _ => Err(RespError::IncorrectFormat),
+            },
+            Resp::WithAttributes { value, .. } => Invalidation::try_from(value.as_ref()),
+            _ => Err(RespError::IncorrectFormat),
+        }
+    }
+}
+
+/// Values cached locally, dropped as their keys are invalidated.
+///
+/// The server stops tracking a key once it has sent its invalidation, so a value should only
+/// be inserted after reading it again. Since invalidations sent while disconnected are lost,
+/// the cache should be cleared when the connection receiving them is lost.
+#[derive(Debug)]
+pub struct TrackingCache<V> {
+    entries: HashMap<Vec<u8>, V>,
+}
+
+impl<V> Default for TrackingCache<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> TrackingCache<V> {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&V> {
+        self.entries.get(key)
+    }
+
+    pub fn insert(&mut self, key: Vec<u8>, value: V) -> Option<V> {
+        self.entries.insert(key, value)
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<V> {
+        self.entries.remove(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Drops the values of the invalidated keys, returning how many were cached.
+    pub fn invalidate(&mut self, invalidation: &Invalidation) -> usize {
+        match invalidation {
+            Invalidation::Keys(keys) => keys
+                .iter()
+                .filter(|key| self.entries.remove(**key).is_some())
+                .count(),
+            Invalidation::All => {
+                let len = self.entries.len();
+                self.entries.clear();
+                len
+            }
+        }
+    }
+
+    /// Applies the frame if it is an invalidation, returning whether it was one.
+    pub fn process(&mut self, frame: &Resp) -> bool {
+        match Invalidation::try_from(frame) {
+            Ok(invalidation) => {
+                self.invalidate(&invalidation);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_resp;
+
+    fn invalidation(input: &[u8]) -> Result<Invalidation<'_>, RespError> {
+        let (resp, left) = parse_resp(input).unwrap();
+        assert!(left.is_empty());
+        Invalidation::try_from(&resp)
+    }
+
+    #[test]
+    pub fn test_invalidations() {
+        assert_eq!(
+            invalidation(b">2\r\n$10\r\ninvalidate\r\n*2\r\n$1\r\na\r\n$1\r\nb\r\n").unwrap(),
+            Invalidation::Keys(vec![b"a", b"b"])
+        );
+        assert_eq!(
+            invalidation(b">2\r\n$10\r\ninvalidate\r\n_\r\n").unwrap(),
+            Invalidation::All
+        );
+        assert_eq!(
+            invalidation(
+                b"*3\r\n$7\r\nmessage\r\n$20\r\n__redis__:invalidate\r\n*1\r\n$1\r\na\r\n"
+            )
+            .unwrap(),
+            Invalidation::Keys(vec![b"a"])
+        );
+        assert_eq!(
+            invalidation(b"*3\r\n$7\r\nmessage\r\n$20\r\n__redis__:invalidate\r\n*-1\r\n").unwrap(),
+            Invalidation::All
+        );
+        assert!(invalidation(b"*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$1\r\na\r\n").is_err());
+        assert!(invalidation(b">3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$1\r\na\r\n").is_err());
+    }
+
+    #[test]
+    pub fn test_cache() {
+        let mut cache = TrackingCache::new();
+        cache.insert(b"a".to_vec(), 1);
+        cache.insert(b"b".to_vec(), 2);
+        cache.insert(b"c".to_vec(), 3);
+
+        let (push, _) =
+            parse_resp(b">2\r\n$10\r\ninvalidate\r\n*2\r\n$1\r\na\r\n$1\r\nx\r\n").unwrap();
+        assert!(cache.process(&push));
+        assert_eq!(cache.get(b"a"), None);
+        assert_eq!(cache.get(b"b"), Some(&2));
+        let (reply, _) = parse_resp(b"$1\r\na\r\n").unwrap();
+        assert!(!cache.process(&reply));
+        assert_eq!(cache.len(), 2);
+
+        assert_eq!(cache.invalidate(&Invalidation::Keys(vec![b"b"])), 1);
+        assert_eq!(cache.invalidate(&Invalidation::All), 1);
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+use crate::client::encode_command;
+use crate::error::{RedisError, RedisErrorKind};
+use crate::{Resp, RespError};
+use std::io::Write;
+
+/// Commands to run atomically between `MULTI` and `EXEC`.
+///
+/// Keys to `WATCH` must be watched beforehand, on the same connection. The replies to the whole
+/// batch, from `MULTI` to `EXEC`, are then interpreted with [`Transaction::interpret`].
+#[derive(Clone, Debug, Default)]
+pub struct Transaction {
+    output: Vec<u8>,
+    count: usize,
+}
+
+/// What became of a transaction once `EXEC` replied.
+#[derive(Debug, Eq, PartialEq)]
+pub enum TransactionOutcome<'a> {
+    /// Every command ran, some possibly failing, with one result per command.
+    Executed(Vec<Result<Resp<'a>, &'a [u8]>>),
+    /// A watched key was modified, nothing ran.
+    Aborted,
+    /// Some commands were rejected when queued so nothing ran, with the index and error of
+    /// each rejected command.
+    Discarded(Vec<(usize, &'a [u8])>),
+}
+
+fn error_message<'a>(resp: &Resp<'a>) -> Option<&'a [u8]> {
+    match resp {
+        Resp::Error(e) | Resp::BlobError(e) => Some(e),
+        _ => None,
+    }
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cmd<A: AsRef<[u8]>>(&mut self, args: &[A]) -> &mut Self {
+        if self.output.is_empty() {
+            // Writing to a Vec cannot fail
+            encode_command(&["MULTI"], &mut self.output).unwrap();
+        }
+        encode_command(args, &mut self.output).unwrap();
+        self.count += 1;
+        self
+    }
+
+    /// Number of queued commands, not counting `MULTI` and `EXEC`.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Writes `MULTI`, the queued commands and `EXEC` in one go.
+    pub fn write_to_writer<W: Write>(&self, writer: &mut W) -> Result<(), RespError> {
+        if self.is_empty() {
+            encode_command(&["MULTI"], writer)?;
+        } else {
+            writer.write_all(&self.output)?;
+        }
+        encode_command(&["EXEC"], writer)
+    }
+
+    /// Interprets the replies to the batch written by [`Transaction::write_to_writer`], from
+    /// the reply to `MULTI` to the reply to `EXEC`.
+    ///
+    /// Fails with [`RespError::IncorrectFormat`] if the replies do not match the batch.
+    pub fn interpret<'a, I>(&self, replies: I) -> Result<TransactionOutcome<'a>, RespError>
+    where
+        I: IntoIterator<Item = Resp<'a>>,
+    {
+        let mut replies = replies.into_iter().map(Resp::without_attributes);
+        match replies.next() {
+            Some(Resp::String(s)) if s == b"OK" => {}
+            _ => return Err(RespError::IncorrectFormat),
+        }
+        let mut rejected = Vec::new();
+        for index in 0..self.count {
+            match replies.next() {
+                Some(Resp::String(s)) if s == b"QUEUED" => {}
+                Some(reply) => match error_message(&reply) {
+                    Some(e) => rejected.push((index, e)),
+                    None => return Err(RespError::IncorrectFormat),
+                },
+                None => return Err(RespError::IncorrectFormat),
+            }
+        }
+        let outcome = match replies.next() {
+            Some(Resp::NilArray | Resp::Null) => TransactionOutcome::Aborted,
+            Some(Resp::Array(a)) if a.len() == self.count && rejected.is_empty() => {
+                TransactionOutcome::Executed(
+                    a.into_iter()
+                        .map(|reply| match error_message(&reply) {
+                            Some(e) => Err(e),
+                            None => Ok(reply),
+                        })
+                        .collect(),
+                )
+            }
+            Some(Resp::Error(e))
+                if RedisError::parse(e).kind == RedisErrorKind::ExecAbort
+                    && !rejected.is_empty() =>
+            {
+                TransactionOutcome::Discarded(rejected)
+            }
+            _ => return Err(RespError::IncorrectFormat),
+        };
+        if replies.next().is_some() {
+            return Err(RespError::IncorrectFormat);
+        }
+        Ok(outcome)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse_resp;
+
+    fn replies(mut input: &[u8]) -> Vec<Resp<'_>> {
+        let mut result = Vec::new();
+        while !input.is_empty() {
+            let (resp, left) = parse_resp(input).unwrap();
+            result.push(resp);
+            input = left;
+        }
+        result
+    }
+
+    fn transaction() -> Transaction {
+        let mut tx = Transaction::new();
+        tx.cmd(&["SET", "k", "v"]).cmd(&["INCR", "k"]);
+        tx
+    }
+
+    #[test]
+    pub fn test_encode() {
+        let mut output = Vec::new();
+        transaction().write_to_writer(&mut output).unwrap();
+        assert_eq!(
+            output,
+            b"*1\r\n$5\r\nMULTI\r\n*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n*2\r\n$4\r\nINCR\r\n$1\r\nk\r\n*1\r\n$4\r\nEXEC\r\n".to_vec()
+        );
+    }
+
+    #[test]
+    pub fn test_outcomes() {
+        let tx = transaction();
+        let input = b"+OK\r\n+QUEUED\r\n+QUEUED\r\n*2\r\n+OK\r\n-ERR value is not an integer\r\n";
+        assert_eq!(
+            tx.interpret(replies(input)).unwrap(),
+            TransactionOutcome::Executed(vec![
+                Ok(Resp::String(b"OK")),
+                Err(&b"ERR value is not an integer"[..])
+            ])
+        );
+        let input = b"+OK\r\n+QUEUED\r\n+QUEUED\r\n*-1\r\n";
+        assert_eq!(
+            tx.interpret(replies(input)).unwrap(),
+            TransactionOutcome::Aborted
+        );
+        let input = b"+OK\r\n+QUEUED\r\n+QUEUED\r\n_\r\n";
+        assert_eq!(
+            tx.interpret(replies(input)).unwrap(),
+            TransactionOutcome::Aborted
+        );
+        let input =
+            b"+OK\r\n+QUEUED\r\n-ERR unknown command\r\n-EXECABORT Transaction discarded\r\n";
+        assert_eq!(
+            tx.interpret(replies(input)).unwrap(),
+            TransactionOutcome::Discarded(vec![(1, &b"ERR unknown command"[..])])
+        );
+    }
+
+    #[test]
+    pub fn test_mismatched_replies() {
+        let tx = transaction();
+        let input = b"+OK\r\n+QUEUED\r\n*2\r\n+OK\r\n:1\r\n";
+        assert!(tx.interpret(replies(input)).is_err());
+        let input = b"+OK\r\n+QUEUED\r\n+QUEUED\r\n*1\r\n+OK\r\n";
+        assert!(tx.interpret(replies(input)).is_err());
+        let input = b"-ERR MULTI calls can not be nested\r\n+QUEUED\r\n+QUEUED\r\n*-1\r\n";
+        assert!(tx.interpret(replies(input)).is_err());
+    }
+}
@@ -0,0 +1,380 @@
+use crate::{Resp, RespError};
+use std::cell::Cell;
+use std::io::Write;
+use std::rc::Rc;
+
+/// Writes an array element by element instead of requiring a fully built [`Resp::Array`].
+///
+/// With [`ArrayWriter::new`] the `*N` header is written upfront and exactly `N` elements must
+/// follow. With [`ArrayWriter::streamed`] the RESP3 `*?` header is written instead and
+/// [`ArrayWriter::finish`] terminates the array with `.\r\n`.
+///
+/// RESP3 maps and sets can be written the same way with [`ArrayWriter::map`],
+/// [`ArrayWriter::set`] and their streamed counterparts. Keys and values of a map are written
+/// as separate elements.
+///
+/// Dropping a writer without calling `finish` terminates a streamed array on a best-effort
+/// basis, unless a write failed in this array or in one nested in it, since the frame is then
+/// cut short anyway. Only `finish` reports a fixed-size array that did not receive exactly `N`
+/// elements.
+pub struct ArrayWriter<W: Write> {
+    writer: W,
+    // Element count to expect, keys and values are counted separately for maps
+    expected: Option<usize>,
+    written: usize,
+    is_map: bool,
+    finished: bool,
+    // Shared with the nested arrays
+    failed: Rc<Cell<bool>>,
+}
+
+impl<W: Write> ArrayWriter<W> {
+    fn with_header(
+        mut writer: W,
+        header: &str,
+        expected: Option<usize>,
+        failed: Rc<Cell<bool>>,
+    ) -> Result<Self, RespError> {
+        if let Err(e) = writer.write_all(header.as_bytes()) {
+            failed.set(true);
+            return Err(e.into());
+        }
+        Ok(Self {
+            writer,
+            expected,
+            written: 0,
+            is_map: header.starts_with('%'),
+            finished: false,
+            failed,
+        })
+    }
+
+    pub fn new(writer: W, len: usize) -> Result<Self, RespError> {
+        Self::with_header(writer, &format!("*{}\r\n", len), Some(len), Rc::default())
+    }
+
+    pub fn streamed(writer: W) -> Result<Self, RespError> {
+        Self::with_header(writer, "*?\r\n", None, Rc::default())
+    }
+
+    /// Starts a map of `len` pairs, for which `2 * len` elements must be written.
+    pub fn map(writer: W, len: usize) -> Result<Self, RespError> {
+        Self::with_header(
+            writer,
+            &format!("%{}\r\n", len),
+            Some(len.checked_mul(2).ok_or(RespError::IncorrectFormat)?),
+            Rc::default(),
+        )
+    }
+
+    pub fn streamed_map(writer: W) -> Result<Self, RespError> {
+        Self::with_header(writer, "%?\r\n", None, Rc::default())
+    }
+
+    pub fn set(writer: W, len: usize) -> Result<Self, RespError> {
+        Self::with_header(writer, &format!("~{}\r\n", len), Some(len), Rc::default())
+    }
+
+    pub fn streamed_set(writer: W) -> Result<Self, RespError> {
+        Self::with_header(writer, "~?\r\n", None, Rc::default())
+    }
+
+    /// Number of elements written so far.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    fn count_element(&mut self) -> Result<(), RespError> {
+        match self.expected {
+            Some(expected) if self.written >= expected => Err(RespError::IncorrectFormat),
+            _ => {
+                self.written += 1;
+                Ok(())
+            }
+        }
+    }
+
+    // Remembers that the frame was cut short by a failed write.
+    fn check<T>(&self, result: Result<T, RespError>) -> Result<T, RespError> {
+        if result.is_err() {
+            self.failed.set(true);
+        }
+        result
+    }
+
+    pub fn write_element(&mut self, element: &Resp) -> Result<(), RespError> {
+        self.count_element()?;
+        let result = element.write_to_writer(&mut self.writer);
+        self.check(result)
+    }
+
+    /// Starts an array nested in this one, counting as a single element.
+    pub fn nested(&mut self, len: usize) -> Result<ArrayWriter<&mut W>, RespError> {
+        self.count_element()?;
+        let header = format!("*{}\r\n", len);
+        ArrayWriter::with_header(&mut self.writer, &header, Some(len), self.failed.clone())
+    }
+
+    /// Starts a streamed array nested in this one, counting as a single element.
+    pub fn nested_streamed(&mut self) -> Result<ArrayWriter<&mut W>, RespError> {
+        self.count_element()?;
+        ArrayWriter::with_header(&mut self.writer, "*?\r\n", None, self.failed.clone())
+    }
+
+    /// Checks that the array is complete and terminates it if it is streamed.
+    pub fn finish(mut self) -> Result<(), RespError> {
+        self.finished = true;
+        match self.expected {
+            Some(expected) if expected != self.written => Err(RespError::IncorrectFormat),
+            Some(_) => Ok(()),
+            // A streamed map cannot end between a key and its value
+            None if self.written % 2 == 1 && self.is_map => Err(RespError::IncorrectFormat),
+            None => {
+                let result = self.writer.write_all(b".\r\n").map_err(RespError::from);
+                self.check(result)
+            }
+        }
+    }
+}
+
+impl<W: Write> Drop for ArrayWriter<W> {
+    fn drop(&mut self) {
+        if !self.finished && !self.failed.get() && self.expected.is_none() {
+            let _ = self.writer.write_all(b".\r\n");
+        }
+    }
+}
+
+/// Writes a RESP3 streamed string (`$?`), one chunk at a time.
+///
+/// Empty chunks are skipped since a zero-length chunk terminates the string. Dropping the
+/// writer without calling [`StreamedStringWriter::finish`] terminates it on a best-effort basis,
+/// unless writing a chunk failed.
+pub struct StreamedStringWriter<W: Write> {
+    writer: W,
+    finished: bool,
+    failed: bool,
+}
+
+impl<W: Write> StreamedStringWriter<W> {
+    pub fn new(mut writer: W) -> Result<Self, RespError> {
+        writer.write_all(b"$?\r\n")?;
+        Ok(Self {
+            writer,
+            finished: false,
+            failed: false,
+        })
+    }
+
+    pub fn write_chunk(&mut self, chunk: &[u8]) -> Result<(), RespError> {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        let header = format!(";{}\r\n", chunk.len());
+        let result = self
+            .writer
+            .write_all(header.as_bytes())
+            .and_then(|_| self.writer.write_all(chunk))
+            .and_then(|_| self.writer.write_all(b"\r\n"));
+        if result.is_err() {
+            self.failed = true;
+        }
+        Ok(result?)
+    }
+
+    pub fn finish(mut self) -> Result<(), RespError> {
+        self.finished = true;
+        self.writer.write_all(b";0\r\n")?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for StreamedStringWriter<W> {
+    fn drop(&mut self) {
+        if !self.finished && !self.failed {
+            let _ = self.writer.write_all(b";0\r\n");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_fixed_array() {
+        let mut output = Vec::new();
+        let mut writer = ArrayWriter::new(&mut output, 3).unwrap();
+        writer.write_element(&Resp::BulkString(b"foo")).unwrap();
+        let mut nested = writer.nested(2).unwrap();
+        nested.write_element(&Resp::Integer(b"1")).unwrap();
+        nested.write_element(&Resp::NilBulk).unwrap();
+        nested.finish().unwrap();
+        writer.write_element(&Resp::String(b"OK")).unwrap();
+        assert!(writer.write_element(&Resp::NilArray).is_err());
+        writer.finish().unwrap();
+        assert_eq!(
+            output,
+            b"*3\r\n$3\r\nfoo\r\n*2\r\n:1\r\n$-1\r\n+OK\r\n".to_vec()
+        );
+        let (resp, left) = crate::parse_resp(&output).unwrap();
+        assert!(matches!(resp, Resp::Array(a) if a.len() == 3));
+        assert!(left.is_empty());
+    }
+
+    #[test]
+    pub fn test_streamed_array() {
+        let mut output = Vec::new();
+        let mut writer = ArrayWriter::streamed(&mut output).unwrap();
+        for key in [b"a", b"b"] {
+            writer.write_element(&Resp::BulkString(key)).unwrap();
+        }
+        writer.nested_streamed().unwrap().finish().unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            output,
+            b"*?\r\n$1\r\na\r\n$1\r\nb\r\n*?\r\n.\r\n.\r\n".to_vec()
+        );
+    }
+
+    #[test]
+    pub fn test_streamed_map_and_set() {
+        let mut output = Vec::new();
+        let mut writer = ArrayWriter::streamed_map(&mut output).unwrap();
+        writer.write_element(&Resp::String(b"key")).unwrap();
+        let mut array = writer.nested_streamed().unwrap();
+        array.write_element(&Resp::Boolean(true)).unwrap();
+        array.finish().unwrap();
+        writer.finish().unwrap();
+        let mut fixed = ArrayWriter::set(&mut output, 1).unwrap();
+        fixed.write_element(&Resp::Null).unwrap();
+        fixed.finish().unwrap();
+        assert_eq!(
+            output,
+            b"%?\r\n+key\r\n*?\r\n#t\r\n.\r\n.\r\n~1\r\n_\r\n".to_vec()
+        );
+        let mut streamed = Vec::new();
+        let mut set = ArrayWriter::streamed_set(&mut streamed).unwrap();
+        set.write_element(&Resp::Integer(b"1")).unwrap();
+        set.finish().unwrap();
+        assert_eq!(streamed, b"~?\r\n:1\r\n.\r\n".to_vec());
+        let (resp, _) = crate::parse_resp(&streamed).unwrap();
+        assert_eq!(resp, Resp::Set(vec![Resp::Integer(b"1")]));
+        let (resp, left) = crate::parse_resp(&output).unwrap();
+        assert_eq!(
+            resp,
+            Resp::Map(vec![(
+                Resp::String(b"key"),
+                Resp::Array(vec![Resp::Boolean(true)])
+            )])
+        );
+        assert_eq!(left, b"~1\r\n_\r\n");
+
+        let mut output = Vec::new();
+        let mut writer = ArrayWriter::streamed_map(&mut output).unwrap();
+        writer.write_element(&Resp::String(b"key")).unwrap();
+        assert!(matches!(writer.finish(), Err(RespError::IncorrectFormat)));
+    }
+
+    #[test]
+    pub fn test_streamed_string() {
+        let mut output = Vec::new();
+        let mut writer = StreamedStringWriter::new(&mut output).unwrap();
+        writer.write_chunk(b"Hell").unwrap();
+        writer.write_chunk(b"").unwrap();
+        writer.write_chunk(b"o").unwrap();
+        writer.finish().unwrap();
+        assert_eq!(output, b"$?\r\n;4\r\nHell\r\n;1\r\no\r\n;0\r\n".to_vec());
+        let (resp, left) = crate::parse_resp(&output).unwrap();
+        assert_eq!(resp, Resp::StreamedString(vec![b"Hell", b"o"]));
+        assert!(left.is_empty());
+    }
+
+    #[test]
+    pub fn test_incomplete_array() {
+        let mut output = Vec::new();
+        let mut writer = ArrayWriter::new(&mut output, 2).unwrap();
+        writer.write_element(&Resp::Integer(b"1")).unwrap();
+        assert!(matches!(writer.finish(), Err(RespError::IncorrectFormat)));
+    }
+
+    #[test]
+    pub fn test_incomplete_array_dropped() {
+        let mut output = Vec::new();
+        let mut writer = ArrayWriter::new(&mut output, 2).unwrap();
+        writer.write_element(&Resp::Integer(b"1")).unwrap();
+        drop(writer);
+        assert_eq!(output, b"*2\r\n:1\r\n".to_vec());
+    }
+
+    // Accepts a number of bytes, then fails like a closed connection.
+    struct Closing {
+        output: Vec<u8>,
+        left: usize,
+    }
+
+    impl Closing {
+        fn new(left: usize) -> Self {
+            Closing {
+                output: Vec::new(),
+                left,
+            }
+        }
+    }
+
+    impl Write for Closing {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.left == 0 {
+                return Err(std::io::ErrorKind::BrokenPipe.into());
+            }
+            let n = buf.len().min(self.left);
+            self.output.extend_from_slice(&buf[..n]);
+            self.left -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    pub fn test_failed_write_dropped() {
+        let mut writer = ArrayWriter::new(Closing::new(6), 2).unwrap();
+        assert!(writer.write_element(&Resp::BulkString(b"foo")).is_err());
+        drop(writer);
+
+        let mut writer = ArrayWriter::new(Closing::new(8), 2).unwrap();
+        let mut nested = writer.nested(1).unwrap();
+        assert!(nested.write_element(&Resp::Integer(b"1")).is_err());
+        drop(nested);
+        drop(writer);
+
+        // Nothing follows the partial frame of a streamed array
+        let mut closing = Closing::new(6);
+        let mut writer = ArrayWriter::streamed(&mut closing).unwrap();
+        assert!(writer.write_element(&Resp::Integer(b"12")).is_err());
+        drop(writer);
+        assert_eq!(closing.output, b"*?\r\n:1".to_vec());
+    }
+
+    #[test]
+    pub fn test_failed_chunk_dropped() {
+        let mut closing = Closing::new(8);
+        let mut writer = StreamedStringWriter::new(&mut closing).unwrap();
+        assert!(writer.write_chunk(b"Hello").is_err());
+        drop(writer);
+        assert_eq!(closing.output, b"$?\r\n;5\r\n".to_vec());
+
+        let mut closing = Closing::new(10);
+        let resp = Resp::StreamedString(vec![b"Hello"]);
+        assert!(resp.write_to_writer(&mut closing).is_err());
+        assert_eq!(closing.output, b"$?\r\n;5\r\nHe".to_vec());
+    }
+
+    #[test]
+    pub fn test_huge_map() {
+        let result = ArrayWriter::map(Vec::new(), usize::MAX);
+        assert!(matches!(result, Err(RespError::IncorrectFormat)));
+    }
+}